@@ -0,0 +1,173 @@
+use std::io::{self, Read, Write};
+
+use crate::error::Error;
+use crate::range::{RangeDecoder, RangeEncoder};
+
+/// Denominator of bit probabilities
+pub const PROBABILITY_ONE: u32 = 1 << 16;
+
+/**
+    Adaptive probability of a bit being one
+
+    Probability is kept in 16 bits and moved towards every coded bit by a
+    fixed fraction `1 / 2^SHIFT` of the distance, so no division is needed.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitProbability {
+    p: u16,
+}
+
+impl BitProbability {
+    /// Speed of adaptation, smaller values adapt faster
+    pub const SHIFT: u32 = 5;
+
+    /**
+        Creates probability of one half
+    */
+    pub fn new() -> Self {
+        Self {
+            p: (PROBABILITY_ONE / 2) as u16,
+        }
+    }
+
+    /**
+        Probability of a one out of `PROBABILITY_ONE`
+    */
+    pub fn p(&self) -> u16 {
+        self.p
+    }
+
+    /**
+        Moves probability towards coded bit
+    */
+    pub fn update(&mut self, bit: bool) {
+        if bit {
+            self.p += ((PROBABILITY_ONE - self.p as u32) >> Self::SHIFT) as u16;
+        } else {
+            self.p -= self.p >> Self::SHIFT;
+        }
+    }
+}
+
+impl Default for BitProbability {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+    Binary arithmetic encoder
+
+    Codes single bits with probabilities given by the caller, which lets
+    bitwise models drive the coder directly. Probabilities are clamped to
+    `1..PROBABILITY_ONE`, so every bit stays codable. Coding is completed
+    by `finish`.
+
+    ```
+    use arithmetic_coder::{BinaryDecoder, BinaryEncoder, BitProbability};
+
+    let bits = [true, true, false, true, true, true];
+    let mut p = BitProbability::new();
+    let mut encoder = BinaryEncoder::new(Vec::new());
+    for bit in bits.iter() {
+        encoder.encode(*bit, p.p()).unwrap();
+        p.update(*bit);
+    }
+    let compressed = encoder.finish().unwrap();
+
+    let mut p = BitProbability::new();
+    let mut decoder = BinaryDecoder::new(&compressed[..]).unwrap();
+    for bit in bits.iter() {
+        let decoded = decoder.decode(p.p()).unwrap();
+        assert_eq!(decoded, *bit);
+        p.update(decoded);
+    }
+    ```
+*/
+#[derive(Debug)]
+pub struct BinaryEncoder<W: Write> {
+    inner: W,
+    coder: RangeEncoder,
+}
+
+impl<W: Write> BinaryEncoder<W> {
+    const BUFFER_SIZE: usize = 4096;
+
+    /**
+        Creates encoder writing to `inner`
+    */
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            coder: RangeEncoder::new(),
+        }
+    }
+
+    /**
+        Encodes `bit` which is one with probability `p / PROBABILITY_ONE`
+    */
+    pub fn encode(&mut self, bit: bool, p: u16) -> io::Result<()> {
+        let one = p.max(1) as u64;
+        if bit {
+            self.coder.encode(0, one, PROBABILITY_ONE as u64);
+        } else {
+            self.coder.encode(one, PROBABILITY_ONE as u64, PROBABILITY_ONE as u64);
+        }
+        if self.coder.buffered() >= Self::BUFFER_SIZE {
+            self.coder.flush_to(&mut self.inner)?;
+        }
+        Ok(())
+    }
+
+    /**
+        Writes terminating bits, flushes inner writer and returns it
+    */
+    pub fn finish(mut self) -> io::Result<W> {
+        self.coder.finish();
+        self.coder.flush_to(&mut self.inner)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/**
+    Binary arithmetic decoder
+
+    Decodes bits written by `BinaryEncoder`, given the same probabilities.
+*/
+#[derive(Debug)]
+pub struct BinaryDecoder<R: Read> {
+    coder: RangeDecoder<R>,
+}
+
+impl<R: Read> BinaryDecoder<R> {
+    /**
+        Creates decoder reading first bits from `inner`
+    */
+    pub fn new(inner: R) -> Result<Self, Error> {
+        Ok(Self {
+            coder: RangeDecoder::new(inner)?,
+        })
+    }
+
+    /**
+        Decodes bit which is one with probability `p / PROBABILITY_ONE`
+    */
+    pub fn decode(&mut self, p: u16) -> Result<bool, Error> {
+        let one = p.max(1) as u64;
+        let bit = self.coder.frequency(PROBABILITY_ONE as u64) < one;
+        if bit {
+            self.coder.decode(0, one, PROBABILITY_ONE as u64)?;
+        } else {
+            self.coder.decode(one, PROBABILITY_ONE as u64, PROBABILITY_ONE as u64)?;
+        }
+        Ok(bit)
+    }
+
+    /**
+        Returns the inner reader
+    */
+    pub fn into_inner(self) -> R {
+        self.coder.into_inner()
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::thread;
+
+use crate::crc::Crc32c;
+use crate::decoder::ArithmeticDecoder;
+use crate::encoder::ArithmeticEncoder;
+use crate::entropy::Counts;
+use crate::error::Error;
+use crate::model::ModelKind;
+use crate::varint::{read_varint, write_varint};
+
+/// Size of blocks used when none is given
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/**
+    Codes one block with a new model of given kind
+
+    Coded block starts with number of characters and ends with their checksum.
+*/
+pub(crate) fn encode_block(data: &[u8], kind: ModelKind) -> Vec<u8> {
+    let mut encoder = ArithmeticEncoder::with_model(Vec::new(), data.len() as u64, kind.build())
+        .expect("writing to vector can not fail");
+    encoder.write_all(data).expect("writing to vector can not fail");
+    encoder.finish().expect("writing to vector can not fail")
+}
+
+/**
+    Decodes block coded by `encode_block` holding at most `block_size` characters
+*/
+pub(crate) fn decode_block(code: &[u8], kind: ModelKind, block_size: u64) -> Result<Vec<u8>, Error> {
+    let mut decoder = ArithmeticDecoder::with_model(code, kind.build())?;
+    if decoder.chars().is_some_and(|chars| chars > block_size) {
+        return Err(Error::Corrupt);
+    }
+    let mut res = Vec::new();
+    decoder.read_to_end(&mut res)?;
+    Ok(res)
+}
+
+/**
+    Reads code of next block, None at the end of stream
+*/
+pub(crate) fn read_code<R: Read>(r: &mut R) -> Result<Option<Vec<u8>>, Error> {
+    let size = read_varint(r)?;
+    if size == 0 {
+        return Ok(None);
+    }
+    let mut code = Vec::new();
+    r.take(size).read_to_end(&mut code)?;
+    if (code.len() as u64) < size {
+        return Err(Error::Truncated);
+    }
+    Ok(Some(code))
+}
+
+/**
+    Codes consecutive blocks of `data` concurrently, one thread per block
+*/
+fn encode_blocks(data: &[u8], kind: ModelKind, block_size: usize) -> Vec<Vec<u8>> {
+    if data.len() <= block_size {
+        return data.chunks(block_size).map(|block| encode_block(block, kind)).collect();
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = data
+            .chunks(block_size)
+            .map(|block| scope.spawn(move || encode_block(block, kind)))
+            .collect();
+        handles.into_iter().map(|h| h.join().expect("coding thread panicked")).collect()
+    })
+}
+
+/**
+    Decodes blocks concurrently, one thread per block
+*/
+fn decode_blocks(codes: &[Vec<u8>], kind: ModelKind, block_size: u64) -> Vec<Result<Vec<u8>, Error>> {
+    if codes.len() <= 1 {
+        return codes.iter().map(|code| decode_block(code, kind, block_size)).collect();
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = codes
+            .iter()
+            .map(|code| scope.spawn(move || decode_block(code, kind, block_size)))
+            .collect();
+        handles.into_iter().map(|h| h.join().expect("coding thread panicked")).collect()
+    })
+}
+
+/**
+    Encoder splitting input into independently coded blocks
+
+    Every `block_size` characters are coded with a new model of given kind
+    and written as their coded length followed by the code, which stores
+    number of characters and their checksum. Coded length zero ends the
+    stream. Damage is therefore confined to one block, and `BlockDecoder`
+    can skip it and continue with the next one. Input of any length can be
+    coded, since blocks are collected in memory before they are written.
+
+    Encoder created by `with_threads` collects as many blocks as it has
+    threads and codes them concurrently. Blocks do not depend on each other,
+    so the output is the same for any number of threads.
+
+    Data written by it is marked with `Header::BLOCKS`. When it is finished
+    by `finish_with_index`, an index of blocks follows the end of stream and
+    the data is additionally marked with `Header::INDEX`.
+*/
+#[derive(Debug)]
+pub struct BlockEncoder<W: Write> {
+    inner: W,
+    kind: ModelKind,
+    block_size: usize,
+    threads: usize,
+    buffer: Vec<u8>, //characters of blocks not coded yet
+    counts: Counts,
+    written: u64, //number of characters written so far
+    index: Vec<(u64, u64)>, //number of characters and size of every written block
+}
+
+impl<W: Write> BlockEncoder<W> {
+    /**
+        Creates encoder of blocks of `block_size` characters and writes their size to `inner`
+
+        Panics if `block_size` is zero.
+    */
+    pub fn new(inner: W, kind: ModelKind, block_size: usize) -> io::Result<Self> {
+        Self::with_threads(inner, kind, block_size, 1)
+    }
+
+    /**
+        Creates encoder coding up to `threads` blocks at once
+
+        Panics if `block_size` or `threads` is zero.
+    */
+    pub fn with_threads(mut inner: W, kind: ModelKind, block_size: usize, threads: usize) -> io::Result<Self> {
+        assert!(block_size > 0, "block size has to be positive");
+        assert!(threads > 0, "number of threads has to be positive");
+        write_varint(&mut inner, block_size as u64)?;
+        Ok(Self {
+            inner,
+            kind,
+            block_size,
+            threads,
+            buffer: Vec::new(),
+            counts: Counts::new(),
+            written: 0,
+            index: Vec::new(),
+        })
+    }
+
+    /**
+        Entropy of characters encoded so far in bits per character
+    */
+    pub fn entropy(&self) -> f32 {
+        self.counts.entropy()
+    }
+
+    /**
+        Number of characters written so far
+    */
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /**
+        Codes last blocks, writes end of stream, flushes inner writer and returns it
+    */
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_blocks()?;
+        write_varint(&mut self.inner, 0)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /**
+        Like `finish`, but writes index of blocks after end of stream
+
+        Index holds number of blocks followed by number of characters and
+        size of every block, all as varints, followed by big-endian CRC-32C
+        of the index and its size as big-endian 64-bit integer, so it can be
+        found from the end of data.
+    */
+    pub fn finish_with_index(mut self) -> io::Result<W> {
+        self.write_blocks()?;
+        write_varint(&mut self.inner, 0)?;
+        let mut index = Vec::new();
+        write_varint(&mut index, self.index.len() as u64)?;
+        for (chars, size) in self.index.iter() {
+            write_varint(&mut index, *chars)?;
+            write_varint(&mut index, *size)?;
+        }
+        let mut crc = Crc32c::new();
+        crc.update(&index);
+        self.inner.write_all(&index)?;
+        self.inner.write_all(&crc.value().to_be_bytes())?;
+        self.inner.write_all(&(index.len() as u64).to_be_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_blocks(&mut self) -> io::Result<()> {
+        let codes = encode_blocks(&self.buffer, self.kind, self.block_size);
+        for (block, code) in self.buffer.chunks(self.block_size).zip(codes) {
+            let mut size = Vec::new();
+            write_varint(&mut size, code.len() as u64)?;
+            self.inner.write_all(&size)?;
+            self.inner.write_all(&code)?;
+            self.index.push((block.len() as u64, (size.len() + code.len()) as u64));
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BlockEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let capacity = self.block_size * self.threads;
+        let n = buf.len().min(capacity - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        for c in &buf[..n] {
+            self.counts.add(*c);
+        }
+        self.written += n as u64;
+        if self.buffer.len() == capacity {
+            self.write_blocks()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/**
+    Decoder of data written by `BlockEncoder`
+
+    Reading returns characters of all blocks in order and fails on the first
+    damaged block. Callers wanting to recover the remaining blocks can call
+    `next_block` directly, which skips a damaged block after reporting it.
+
+    Decoder created by `with_threads` reads as many blocks as it has threads
+    and decodes them concurrently.
+*/
+#[derive(Debug)]
+pub struct BlockDecoder<R: Read> {
+    inner: R,
+    kind: ModelKind,
+    block_size: u64,
+    threads: usize,
+    decoded: VecDeque<Result<Vec<u8>, Error>>, //blocks decoded ahead
+    block: Vec<u8>, //characters of current block
+    position: usize, //index of next character of current block
+    counts: Counts,
+    ended: bool, //whether end of stream was read
+}
+
+impl<R: Read> BlockDecoder<R> {
+    /**
+        Creates decoder of blocks coded with models of given kind, reading block size from `inner`
+    */
+    pub fn new(inner: R, kind: ModelKind) -> Result<Self, Error> {
+        Self::with_threads(inner, kind, 1)
+    }
+
+    /**
+        Creates decoder decoding up to `threads` blocks at once
+
+        Panics if `threads` is zero.
+    */
+    pub fn with_threads(mut inner: R, kind: ModelKind, threads: usize) -> Result<Self, Error> {
+        assert!(threads > 0, "number of threads has to be positive");
+        let block_size = read_varint(&mut inner)?;
+        if block_size == 0 {
+            return Err(Error::Corrupt);
+        }
+        Ok(Self {
+            inner,
+            kind,
+            block_size,
+            threads,
+            decoded: VecDeque::new(),
+            block: Vec::new(),
+            position: 0,
+            counts: Counts::new(),
+            ended: false,
+        })
+    }
+
+    /**
+        Largest number of characters in one block
+    */
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /**
+        Entropy of characters decoded so far in bits per character
+    */
+    pub fn entropy(&self) -> f32 {
+        self.counts.entropy()
+    }
+
+    /**
+        Decodes next block, None at the end of stream
+
+        Whole block is read before it is decoded, so after an error other
+        than `Error::Truncated` or `Error::Io` the next call continues with
+        the following block.
+    */
+    pub fn next_block(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        if self.decoded.is_empty() {
+            self.decode_ahead();
+        }
+        match self.decoded.pop_front() {
+            Some(Ok(block)) => {
+                for c in &block {
+                    self.counts.add(*c);
+                }
+                Ok(Some(block))
+            }
+            Some(Err(e)) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    /**
+        Reads up to `threads` blocks and decodes them
+    */
+    fn decode_ahead(&mut self) {
+        let mut codes = Vec::new();
+        let mut error = None;
+        while !self.ended && codes.len() < self.threads {
+            match read_code(&mut self.inner) {
+                Ok(Some(code)) => codes.push(code),
+                Ok(None) => self.ended = true,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        self.decoded.extend(decode_blocks(&codes, self.kind, self.block_size));
+        if let Some(e) = error {
+            self.decoded.push_back(Err(e));
+        }
+    }
+}
+
+impl<R: Read> Read for BlockDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.block.len() {
+            match self.next_block()? {
+                Some(block) => {
+                    self.block = block;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.block.len() - self.position);
+        buf[..n].copy_from_slice(&self.block[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+        Codes `data` in blocks of `block_size` characters with `threads` threads
+    */
+    fn encode(data: &[u8], block_size: usize, threads: usize) -> Vec<u8> {
+        let mut encoder = BlockEncoder::with_threads(Vec::new(), ModelKind::Ppm(2), block_size, threads).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn output_does_not_depend_on_threads() {
+        let data: Vec<u8> = (0..5000_u32).map(|i| (i * i % 251) as u8).collect();
+        let single = encode(&data, 300, 1);
+        assert_eq!(encode(&data, 300, 4), single);
+        let mut decoded = Vec::new();
+        BlockDecoder::with_threads(&single[..], ModelKind::Ppm(2), 4)
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+}
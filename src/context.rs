@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::fenwick::FenwickTree;
+use crate::model::Model;
+
+/**
+    Frequencies of characters seen in one context
+*/
+#[derive(Debug, Clone)]
+struct Table {
+    frequencies: FenwickTree,
+}
+
+impl Table {
+    const INCREMENT: u32 = 32; //Select weight of one occurrence
+    const LIMIT: u64 = 1 << 16; //Select total after which frequencies are halved
+
+    fn new() -> Self {
+        Self {
+            frequencies: FenwickTree::new(256, 1),
+        }
+    }
+
+    fn add(&mut self, symbol: usize) {
+        self.frequencies.add(symbol, Self::INCREMENT);
+        if self.frequencies.total() > Self::LIMIT {
+            //Halve frequencies keeping every character possible
+            self.frequencies.halve();
+        }
+    }
+}
+
+/**
+    Order-k finite-context model
+
+    Keeps separate frequencies of characters for every combination of `order`
+    preceding characters, so characters are predicted from what came directly
+    before them. Frequencies are updated after every character and kept in
+    Fenwick trees, so both updates and lookups take logarithmic time.
+    Order 0 uses a single table for the whole input. When the number of
+    seen contexts exceeds `MAX_CONTEXTS` all tables are discarded to bound
+    memory use.
+*/
+#[derive(Debug, Clone)]
+pub struct ContextModel {
+    order: u8,
+    history: u64, //last `order` characters, most recent in lowest byte
+    tables: Vec<Table>,
+    index: HashMap<u64, usize>,
+    current: usize, //index of table of current context
+}
+
+impl ContextModel {
+    /// Largest supported order
+    pub const MAX_ORDER: u8 = 8;
+    /// Number of contexts kept before all of them are discarded
+    pub const MAX_CONTEXTS: usize = 1 << 16;
+
+    /**
+        Creates model of given order
+
+        Panics if `order` is greater than `MAX_ORDER`.
+    */
+    pub fn new(order: u8) -> Self {
+        assert!(order <= Self::MAX_ORDER, "order {} is greater than {}", order, Self::MAX_ORDER);
+        let mut model = Self {
+            order,
+            history: 0,
+            tables: Vec::new(),
+            index: HashMap::new(),
+            current: 0,
+        };
+        model.select_context();
+        model
+    }
+
+    /**
+        Order of the model
+    */
+    pub fn order(&self) -> u8 {
+        self.order
+    }
+
+    /**
+        Makes table of context given by history current, creating it if needed
+    */
+    fn select_context(&mut self) {
+        if let Some(i) = self.index.get(&self.history) {
+            self.current = *i;
+            return;
+        }
+        if self.index.len() == Self::MAX_CONTEXTS {
+            //Reuse memory of discarded tables
+            self.index.clear();
+        }
+        self.current = self.index.len();
+        if self.current < self.tables.len() {
+            self.tables[self.current].frequencies.reset(1);
+        } else {
+            self.tables.push(Table::new());
+        }
+        self.index.insert(self.history, self.current);
+    }
+}
+
+impl Model for ContextModel {
+    fn total(&self) -> u64 {
+        self.tables[self.current].frequencies.total()
+    }
+
+    fn range(&self, symbol: usize) -> (u64, u64) {
+        self.tables[self.current].frequencies.range(symbol)
+    }
+
+    fn symbol(&self, frequency: u64) -> usize {
+        self.tables[self.current].frequencies.find(frequency)
+    }
+
+    fn update(&mut self, symbol: usize) {
+        self.tables[self.current].add(symbol);
+        if self.order > 0 {
+            let mask = u64::MAX >> (64 - 8 * self.order as u32);
+            self.history = ((self.history << 8) | symbol as u64) & mask;
+            self.select_context();
+        }
+    }
+}
@@ -0,0 +1,73 @@
+/**
+    CRC-32C (Castagnoli) checksum
+
+    Computed byte by byte with a table generated at compile time.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Crc32c {
+    value: u32,
+}
+
+const POLYNOMIAL: u32 = 0x82F63B78; //Reversed Castagnoli polynomial
+
+const TABLE: [u32; 256] = table();
+
+const fn table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 { (value >> 1) ^ POLYNOMIAL } else { value >> 1 };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+impl Crc32c {
+    pub(crate) fn new() -> Self {
+        Self { value: 0xFFFFFFFF }
+    }
+
+    /**
+        Adds `data` to checksum
+    */
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for b in data {
+            self.value = (self.value >> 8) ^ TABLE[((self.value ^ *b as u32) & 0xFF) as usize];
+        }
+    }
+
+    /**
+        Checksum of data added so far
+    */
+    pub(crate) fn value(&self) -> u32 {
+        !self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_answer() {
+        let mut crc = Crc32c::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.value(), 0xE3069283);
+    }
+
+    #[test]
+    fn updates_in_parts() {
+        let mut crc = Crc32c::new();
+        crc.update(b"1234");
+        crc.update(b"");
+        crc.update(b"56789");
+        assert_eq!(crc.value(), 0xE3069283);
+        assert_eq!(Crc32c::new().value(), 0);
+    }
+}
@@ -0,0 +1,245 @@
+use std::io::{self, Read};
+
+use crate::crc::Crc32c;
+use crate::entropy::Counts;
+use crate::error::Error;
+use crate::model::{Model, ESCAPE};
+use crate::probabilities::Probabilities;
+use crate::range::RangeDecoder;
+use crate::varint::read_varint;
+
+/**
+    Streaming arithmetic decoder
+
+    Coded bytes are pulled from the inner reader only when the decoder needs
+    more bits, and characters are produced as they are read, so neither the
+    coded nor the decoded data has to be held in memory.
+
+    The decoder must use the same `Model` the data was encoded with. After
+    the last character the stored checksum is compared with the decoded
+    characters and `Error::ChecksumMismatch` is returned if they differ.
+*/
+#[derive(Debug)]
+pub struct ArithmeticDecoder<R: Read, M: Model = Probabilities> {
+    coder: RangeDecoder<R>,
+    model: M,
+    counts: Counts,
+    crc: Crc32c,
+    chars: Option<u64>, //number of characters encoded, None if stream is ended by end symbol
+    read: u64, //number of characters decoded so far
+    ended: bool, //whether end symbol was decoded
+    stored: Option<u32>, //checksum following the code, once it was read
+}
+
+impl<R: Read> ArithmeticDecoder<R> {
+    /**
+        Creates decoder reading number of encoded characters and first bits from `inner`
+    */
+    pub fn new(inner: R) -> Result<Self, Error> {
+        Self::with_model(inner, Probabilities::new())
+    }
+}
+
+impl<R: Read, M: Model> ArithmeticDecoder<R, M> {
+    /**
+        Creates decoder of data coded with `model`
+    */
+    pub fn with_model(mut inner: R, model: M) -> Result<Self, Error> {
+        let chars = read_varint(&mut inner)?;
+        Ok(Self {
+            coder: RangeDecoder::new(inner)?,
+            model,
+            counts: Counts::new(),
+            crc: Crc32c::new(),
+            chars: Some(chars),
+            read: 0,
+            ended: false,
+            stored: None,
+        })
+    }
+
+    /**
+        Creates decoder of data written by `ArithmeticEncoder::with_end_symbol`
+    */
+    pub fn with_end_symbol(inner: R, model: M) -> Result<Self, Error> {
+        Ok(Self {
+            coder: RangeDecoder::new(inner)?,
+            model,
+            counts: Counts::new(),
+            crc: Crc32c::new(),
+            chars: None,
+            read: 0,
+            ended: false,
+            stored: None,
+        })
+    }
+
+    /**
+        Number of characters encoded in the stream, None if it is not stored
+    */
+    pub fn chars(&self) -> Option<u64> {
+        self.chars
+    }
+
+    /**
+        Number of characters decoded so far
+    */
+    pub fn decoded(&self) -> u64 {
+        self.read
+    }
+
+    /**
+        Entropy of characters decoded so far in bits per character
+    */
+    pub fn entropy(&self) -> f32 {
+        self.counts.entropy()
+    }
+
+    /**
+        CRC-32C of characters decoded so far
+    */
+    pub fn checksum(&self) -> u32 {
+        self.crc.value()
+    }
+
+    /**
+        Returns the inner reader
+
+        Bytes read ahead by the decoder are not returned to it.
+    */
+    pub fn into_inner(self) -> R {
+        self.coder.into_inner()
+    }
+
+    /**
+        Whether all characters were decoded, reading end flag if needed
+    */
+    fn at_end(&mut self) -> Result<bool, Error> {
+        match self.chars {
+            Some(chars) => Ok(self.read == chars),
+            None => {
+                if !self.ended {
+                    self.ended = self.coder.decode_end()?;
+                }
+                Ok(self.ended)
+            }
+        }
+    }
+
+    /**
+        Compares checksum following the code with decoded characters
+    */
+    fn verify(&mut self) -> Result<(), Error> {
+        let stored = match self.stored {
+            Some(stored) => stored,
+            None => {
+                let mut stored = [0; 4];
+                self.coder.read_trailer(&mut stored)?;
+                *self.stored.insert(u32::from_be_bytes(stored))
+            }
+        };
+        if stored != self.crc.value() {
+            return Err(Error::ChecksumMismatch);
+        }
+        Ok(())
+    }
+
+    fn decode(&mut self) -> Result<u8, Error> {
+        let c = if self.model.binary() {
+            let mut c = 0;
+            for _ in 0..8 {
+                c = (c << 1) | self.decode_symbol()?;
+            }
+            c
+        } else {
+            loop {
+                let symbol = self.decode_symbol()?;
+                if symbol != ESCAPE {
+                    break symbol;
+                }
+            }
+        };
+        self.read += 1;
+        self.counts.add(c as u8);
+        Ok(c as u8)
+    }
+
+    /**
+        Decodes one symbol of the model and updates it
+    */
+    fn decode_symbol(&mut self) -> Result<usize, Error> {
+        let total = self.model.total();
+        if total == 0 {
+            //Only corrupt data escapes from every symbol
+            return Err(Error::Corrupt);
+        }
+        let symbol = self.model.symbol(self.coder.frequency(total));
+        let (start, end) = self.model.range(symbol);
+        self.coder.decode(start, end, total)?;
+        self.model.update(symbol);
+        Ok(symbol)
+    }
+}
+
+impl<R: Read, M: Model> Read for ArithmeticDecoder<R, M> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() && !self.at_end()? {
+            buf[n] = self.decode()?;
+            n += 1;
+        }
+        self.crc.update(&buf[..n]);
+        if self.ended || self.chars == Some(self.read) {
+            self.verify()?;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+
+    #[test]
+    fn flipped_byte_is_mismatch() {
+        let data = b"abracadabra".repeat(100);
+        let mut compressed = crate::compress(&data);
+        //Flip in one of the last bytes of code changes only the last characters
+        let last = compressed.len() - 4 - 3;
+        compressed[last] ^= 0x10;
+        let mut decoder = ArithmeticDecoder::new(&compressed[Header::SIZE..]).unwrap();
+        let e = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(Error::from(e), Error::ChecksumMismatch));
+    }
+
+    #[test]
+    fn flip_never_gives_wrong_data() {
+        let data = b"abracadabra".repeat(10);
+        let compressed = crate::compress(&data);
+        for i in Header::SIZE..compressed.len() {
+            for bit in 0..8 {
+                let mut flipped = compressed.clone();
+                flipped[i] ^= 1 << bit;
+                //Padding of the last byte of code may change without changing the data
+                if let Ok(decompressed) = crate::decompress(&flipped) {
+                    assert_eq!(decompressed, data, "flip of bit {} of byte {} not detected", bit, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mismatch_is_repeated() {
+        let data = b"abracadabra".repeat(10);
+        let mut compressed = crate::compress(&data);
+        *compressed.last_mut().unwrap() ^= 1;
+        compressed.extend_from_slice(&[0; 300]);
+        let mut decoder = ArithmeticDecoder::new(&compressed[Header::SIZE..]).unwrap();
+        let mut buf = vec![0; data.len()];
+        for _ in 0..2 {
+            let e = decoder.read(&mut buf).unwrap_err();
+            assert!(matches!(Error::from(e), Error::ChecksumMismatch));
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::io::{self, Write};
+
+use crate::crc::Crc32c;
+use crate::entropy::Counts;
+use crate::model::{Model, ESCAPE};
+use crate::probabilities::Probabilities;
+use crate::range::RangeEncoder;
+use crate::varint::write_varint;
+
+/**
+    Streaming arithmetic encoder
+
+    Characters written to the encoder are coded immediately and finished bytes
+    are pushed to the inner writer at the end of every `write` call, so memory
+    use does not depend on the size of the input. Number of characters has to be
+    known up front, because it is stored in front of the coded data as a
+    varint, unless the encoder is created with `with_end_symbol`. Coding is
+    completed by `finish`; dropping the encoder without calling it leaves the
+    output truncated. Coded data is followed by a big-endian CRC-32C of the
+    characters, which `ArithmeticDecoder` verifies. Encoder finished without
+    any characters writes a complete stream as well, which decodes to no
+    characters.
+
+    Characters are coded with the default order-0 `Probabilities` model unless
+    another `Model` is given to `with_model`.
+*/
+#[derive(Debug)]
+pub struct ArithmeticEncoder<W: Write, M: Model = Probabilities> {
+    inner: W,
+    model: M,
+    counts: Counts,
+    crc: Crc32c,
+    coder: RangeEncoder,
+    chars: Option<u64>, //number of characters announced, None if stream is ended by end symbol
+    written: u64, //number of characters encoded so far
+}
+
+impl<W: Write> ArithmeticEncoder<W> {
+    /**
+        Creates encoder of `chars` characters and writes their number to `inner`
+    */
+    pub fn new(inner: W, chars: u64) -> io::Result<Self> {
+        Self::with_model(inner, chars, Probabilities::new())
+    }
+}
+
+impl<W: Write, M: Model> ArithmeticEncoder<W, M> {
+    /**
+        Creates encoder of `chars` characters coded with `model`
+    */
+    pub fn with_model(mut inner: W, chars: u64, model: M) -> io::Result<Self> {
+        write_varint(&mut inner, chars)?;
+        Ok(Self {
+            inner,
+            model,
+            counts: Counts::new(),
+            crc: Crc32c::new(),
+            coder: RangeEncoder::new(),
+            chars: Some(chars),
+            written: 0,
+        })
+    }
+
+    /**
+        Creates encoder of any number of characters coded with `model`
+
+        Number of characters is not stored. Instead every character is
+        preceded by a flag coded with the smallest possible probability of
+        ending the stream, which `finish` codes as set. This costs a few bits
+        per gigabyte and lets input of unknown length, such as a pipe, be
+        compressed without buffering it.
+    */
+    pub fn with_end_symbol(inner: W, model: M) -> Self {
+        Self {
+            inner,
+            model,
+            counts: Counts::new(),
+            crc: Crc32c::new(),
+            coder: RangeEncoder::new(),
+            chars: None,
+            written: 0,
+        }
+    }
+
+    /**
+        Number of characters encoded so far
+    */
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /**
+        Entropy of characters encoded so far in bits per character
+    */
+    pub fn entropy(&self) -> f32 {
+        self.counts.entropy()
+    }
+
+    /**
+        CRC-32C of characters encoded so far
+    */
+    pub fn checksum(&self) -> u32 {
+        self.crc.value()
+    }
+
+    /**
+        Writes terminating bits and checksum, flushes inner writer and returns it
+    */
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.chars {
+            Some(chars) if chars != self.written => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("encoded {} characters instead of {}", self.written, chars),
+                ));
+            }
+            Some(_) => {}
+            None => self.coder.encode_end(true),
+        }
+        self.coder.finish();
+        self.coder.flush_to(&mut self.inner)?;
+        self.inner.write_all(&self.crc.value().to_be_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn encode(&mut self, c: u8) {
+        if self.chars.is_none() {
+            self.coder.encode_end(false);
+        }
+        if self.model.binary() {
+            for i in (0..8).rev() {
+                self.encode_symbol(((c >> i) & 1) as usize);
+            }
+        } else {
+            while !self.model.contains(c as usize) {
+                self.encode_symbol(ESCAPE);
+            }
+            self.encode_symbol(c as usize);
+        }
+        self.counts.add(c);
+    }
+
+    fn encode_symbol(&mut self, symbol: usize) {
+        let total = self.model.total();
+        let (start, end) = self.model.range(symbol);
+        self.coder.encode(start, end, total);
+        self.model.update(symbol);
+    }
+}
+
+impl<W: Write, M: Model> Write for ArithmeticEncoder<W, M> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(chars) = self.chars {
+            if buf.len() as u64 > chars - self.written {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("more than {} characters written to encoder", chars),
+                ));
+            }
+        }
+        for c in buf {
+            self.encode(*c);
+        }
+        self.crc.update(buf);
+        self.written += buf.len() as u64;
+        self.coder.flush_to(&mut self.inner)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use crate::block::DEFAULT_BLOCK_SIZE;
+    use crate::decoder::ArithmeticDecoder;
+    use crate::error::Error;
+
+    fn encode(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ArithmeticEncoder::with_end_symbol(Vec::new(), Probabilities::new());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode(code: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut res = Vec::new();
+        ArithmeticDecoder::with_end_symbol(code, Probabilities::new())?.read_to_end(&mut res)?;
+        Ok(res)
+    }
+
+    #[test]
+    fn end_symbol_round_trip() {
+        let long: Vec<u8> = (0..DEFAULT_BLOCK_SIZE + 1000).map(|i| (i % 7 * 13 + i % 11) as u8).collect();
+        for data in [&b""[..], b"a", &long] {
+            assert_eq!(decode(&encode(data)).unwrap(), data, "{} bytes", data.len());
+        }
+    }
+
+    #[test]
+    fn end_symbol_truncated() {
+        let code = encode(&b"abracadabra".repeat(100));
+        for cut in [1, 4, 5, code.len() / 2] {
+            assert!(matches!(decode(&code[..code.len() - cut]), Err(Error::Truncated)), "cut {}", cut);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+/**
+    Occurrences of characters used to compute their empirical entropy
+*/
+#[derive(Debug)]
+pub(crate) struct Counts {
+    counts: Vec<u64>,
+}
+
+impl Counts {
+    pub(crate) fn new() -> Self {
+        Self {
+            counts: vec![0; 256],
+        }
+    }
+
+    pub(crate) fn add(&mut self, c: u8) {
+        self.counts[c as usize] += 1;
+    }
+
+    /**
+        Entropy of added chars in bits per char
+    */
+    pub(crate) fn entropy(&self) -> f32 {
+        let sum = self.counts.iter().fold(0, |a, b| a+*b);
+        self.counts.iter().fold(0.0, |acc, x| if *x > 0{
+            acc - (*x as f32/ sum as f32) * ((*x) as f32 / sum as f32).log2()
+        }  else{
+            acc
+        })
+    }
+}
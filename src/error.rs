@@ -0,0 +1,72 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/**
+    Errors returned when data can not be compressed or decompressed
+*/
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing failed
+    Io(io::Error),
+    /// Compressed data ends before all characters were decoded
+    Truncated,
+    /// Compressed data does not start with a valid header
+    BadHeader,
+    /// Compressed data was written by a newer format version
+    UnsupportedVersion(u8),
+    /// Decompressed data does not match stored checksum
+    ChecksumMismatch,
+    /// Compressed data is internally inconsistent
+    Corrupt,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Truncated => write!(f, "compressed data is truncated"),
+            Error::BadHeader => write!(f, "compressed data has invalid header"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            Error::ChecksumMismatch => write!(f, "checksum of decompressed data does not match"),
+            Error::Corrupt => write!(f, "compressed data is corrupt"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/**
+    Unwraps errors passed through `io::Read` and `io::Write` implementations
+*/
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            match e.into_inner().map(|inner| inner.downcast::<Error>()) {
+                Some(Ok(inner)) => *inner,
+                _ => unreachable!(),
+            }
+        } else {
+            Error::Io(e)
+        }
+    }
+}
+
+/**
+    Wraps errors so they can be returned from `io::Read` and `io::Write` implementations
+*/
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+/**
+    Binary indexed tree of frequencies
+
+    Keeps cumulative frequencies of symbols so that both updating a frequency
+    and finding the symbol covering a cumulative frequency take O(log n).
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct FenwickTree {
+    tree: Vec<u32>, //tree[i] holds sum of frequencies of symbols (i - lowbit(i))..i
+    total: u64,
+}
+
+impl FenwickTree {
+    /**
+        Creates tree of `n` symbols with frequency `initial` each
+    */
+    pub(crate) fn new(n: usize, initial: u32) -> Self {
+        let mut tree = Self {
+            tree: vec![0; n + 1],
+            total: 0,
+        };
+        tree.reset(initial);
+        tree
+    }
+
+    /**
+        Sets frequency of every symbol to `initial`
+    */
+    pub(crate) fn reset(&mut self, initial: u32) {
+        //Node i covers lowbit(i) symbols
+        for (i, t) in self.tree.iter_mut().enumerate() {
+            *t = initial * (i & i.wrapping_neg()) as u32;
+        }
+        self.total = initial as u64 * (self.tree.len() - 1) as u64;
+    }
+
+    /**
+        Sum of all frequencies
+    */
+    pub(crate) fn total(&self) -> u64 {
+        self.total
+    }
+
+    /**
+        Sum of frequencies of symbols before `symbol`
+    */
+    pub(crate) fn prefix(&self, symbol: usize) -> u64 {
+        let mut sum = 0;
+        let mut i = symbol;
+        while i > 0 {
+            sum += self.tree[i] as u64;
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /**
+        Cumulative frequencies of symbols before `symbol` and up to `symbol` inclusive
+    */
+    pub(crate) fn range(&self, symbol: usize) -> (u64, u64) {
+        (self.prefix(symbol), self.prefix(symbol + 1))
+    }
+
+    /**
+        Symbol whose cumulative frequency range contains `frequency`
+    */
+    pub(crate) fn find(&self, frequency: u64) -> usize {
+        let n = self.tree.len() - 1;
+        let mut position = 0;
+        let mut rest = frequency;
+        let mut step = n.next_power_of_two();
+        while step > 0 {
+            let next = position + step;
+            if next <= n && (self.tree[next] as u64) <= rest {
+                position = next;
+                rest -= self.tree[next] as u64;
+            }
+            step >>= 1;
+        }
+        position.min(n - 1)
+    }
+
+    /**
+        Adds `delta` to frequency of `symbol`
+    */
+    pub(crate) fn add(&mut self, symbol: usize, delta: u32) {
+        let mut i = symbol + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+        self.total += delta as u64;
+    }
+
+    /**
+        Halves all frequencies rounding up, so no symbol gets frequency zero
+    */
+    pub(crate) fn halve(&mut self) {
+        let n = self.tree.len();
+        //Turn partial sums into frequencies, halve them and sum them up again in O(n)
+        for i in (1..n).rev() {
+            let parent = i + (i & i.wrapping_neg());
+            if parent < n {
+                self.tree[parent] -= self.tree[i];
+            }
+        }
+        self.total = 0;
+        for i in 1..n {
+            self.tree[i] = self.tree[i].div_ceil(2);
+            self.total += self.tree[i] as u64;
+        }
+        for i in 1..n {
+            let parent = i + (i & i.wrapping_neg());
+            if parent < n {
+                self.tree[parent] += self.tree[i];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+        Checks `tree` against plain frequencies
+    */
+    fn check(tree: &FenwickTree, frequencies: &[u32]) {
+        let mut sum = 0;
+        for (symbol, frequency) in frequencies.iter().enumerate() {
+            assert_eq!(tree.prefix(symbol), sum);
+            if *frequency > 0 {
+                assert_eq!(tree.find(sum), symbol);
+                assert_eq!(tree.find(sum + *frequency as u64 - 1), symbol);
+            }
+            sum += *frequency as u64;
+        }
+        assert_eq!(tree.prefix(frequencies.len()), sum);
+        assert_eq!(tree.total(), sum);
+    }
+
+    #[test]
+    fn halve_keeps_prefix() {
+        let mut tree = FenwickTree::new(257, 1);
+        let mut frequencies = vec![1; 257];
+        for (i, symbol) in (0..2000).map(|i| (i * 7919) % 257).enumerate() {
+            let delta = (i % 37) as u32;
+            tree.add(symbol, delta);
+            frequencies[symbol] += delta;
+        }
+        check(&tree, &frequencies);
+        for _ in 0..3 {
+            tree.halve();
+            for frequency in frequencies.iter_mut() {
+                *frequency = frequency.div_ceil(2);
+            }
+            check(&tree, &frequencies);
+        }
+    }
+}
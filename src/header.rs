@@ -0,0 +1,106 @@
+use std::io::{self, Read, Write};
+
+use crate::error::Error;
+use crate::model::ModelKind;
+
+/**
+    Header written in front of compressed data
+
+    Layout of version 1, all fields one byte unless noted:
+
+    | field           | size |
+    |-----------------|------|
+    | magic `AAC\x1a` | 4    |
+    | format version  | 1    |
+    | flags           | 1    |
+    | model id        | 1    |
+    | model parameter | 1    |
+
+    Flag `END_SYMBOL` marks data coded by `ArithmeticEncoder::with_end_symbol`
+    and flag `BLOCKS` data split into blocks by `BlockEncoder`, which
+    additionally has flag `INDEX` when it ends with an index of blocks.
+    Readers reject data with other magic, a newer version or flags they do
+    not know, instead of decoding it into garbage.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Format version data was written with
+    pub version: u8,
+    /// Optional features used by the data
+    pub flags: u8,
+    /// Kind of model data was coded with
+    pub model: ModelKind,
+}
+
+impl Header {
+    /// Bytes every compressed file starts with
+    pub const MAGIC: [u8; 4] = *b"AAC\x1a";
+    /// Newest format version
+    pub const VERSION: u8 = 1;
+    /// Size of header in bytes
+    pub const SIZE: usize = 8;
+    /// Flag of data ended by end symbol instead of starting with its length
+    pub const END_SYMBOL: u8 = 1;
+    /// Flag of data split into blocks by `BlockEncoder`
+    pub const BLOCKS: u8 = 2;
+    /// Flag of blocks followed by their index, set only together with `BLOCKS`
+    pub const INDEX: u8 = 4;
+    /// Flags understood by this version
+    const KNOWN_FLAGS: u8 = Self::END_SYMBOL | Self::BLOCKS | Self::INDEX;
+
+    /**
+        Creates header of current version for data coded with `model`
+    */
+    pub fn new(model: ModelKind) -> Self {
+        Self {
+            version: Self::VERSION,
+            flags: 0,
+            model,
+        }
+    }
+
+    /**
+        Writes header to `w`
+    */
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let [id, parameter] = self.model.to_bytes();
+        w.write_all(&Self::MAGIC)?;
+        w.write_all(&[self.version, self.flags, id, parameter])
+    }
+
+    /**
+        Reads and validates header written by `write_to`
+    */
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let mut bytes = [0; Self::SIZE];
+        if let Err(e) = r.read_exact(&mut bytes) {
+            return Err(match e.kind() {
+                io::ErrorKind::UnexpectedEof => Error::Truncated,
+                _ => Error::Io(e),
+            });
+        }
+        if bytes[..4] != Self::MAGIC {
+            return Err(Error::BadHeader);
+        }
+        let version = bytes[4];
+        if version == 0 || version > Self::VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let flags = bytes[5];
+        let blocks = flags & Self::BLOCKS != 0;
+        if flags & !Self::KNOWN_FLAGS != 0
+            || blocks && flags & Self::END_SYMBOL != 0
+            || !blocks && flags & Self::INDEX != 0
+        {
+            return Err(Error::BadHeader);
+        }
+        match ModelKind::from_bytes([bytes[6], bytes[7]]) {
+            Some(model) => Ok(Self {
+                version,
+                flags,
+                model,
+            }),
+            None => Err(Error::BadHeader),
+        }
+    }
+}
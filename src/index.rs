@@ -0,0 +1,119 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::block::{decode_block, read_code};
+use crate::crc::Crc32c;
+use crate::error::Error;
+use crate::model::ModelKind;
+use crate::varint::read_varint;
+
+/**
+    Position of one block in decompressed and compressed data
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    offset: u64, //offset of first character in decompressed data
+    position: u64, //offset of block in compressed data
+    chars: u64, //number of characters in block
+}
+
+/**
+    Index of blocks written by `BlockEncoder::finish_with_index`
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct BlockIndex {
+    entries: Vec<Entry>,
+}
+
+impl BlockIndex {
+    /**
+        Reads index from the end of `r`, first block starts at `start`
+    */
+    pub(crate) fn read_from<R: Read + Seek>(r: &mut R, start: u64) -> Result<Self, Error> {
+        let end = r.seek(SeekFrom::End(0))?;
+        if end < start + 12 {
+            return Err(Error::Truncated);
+        }
+        r.seek(SeekFrom::Start(end - 12))?;
+        let mut crc = [0; 4];
+        let mut size = [0; 8];
+        r.read_exact(&mut crc)?;
+        r.read_exact(&mut size)?;
+        let crc = u32::from_be_bytes(crc);
+        let size = u64::from_be_bytes(size);
+        let end = end - 12; //end of index
+        if size > end - start {
+            return Err(Error::Corrupt);
+        }
+        r.seek(SeekFrom::Start(end - size))?;
+        let mut bytes = Vec::new();
+        r.take(size).read_to_end(&mut bytes)?;
+        let mut checksum = Crc32c::new();
+        checksum.update(&bytes);
+        if checksum.value() != crc {
+            return Err(Error::ChecksumMismatch);
+        }
+        let mut bytes = &bytes[..];
+        let count = read_varint(&mut bytes).map_err(|_| Error::Corrupt)?;
+        let mut entries = Vec::new();
+        let (mut offset, mut position) = (0_u64, start);
+        for _ in 0..count {
+            let chars = read_varint(&mut bytes).map_err(|_| Error::Corrupt)?;
+            let size = read_varint(&mut bytes).map_err(|_| Error::Corrupt)?;
+            entries.push(Entry {
+                offset,
+                position,
+                chars,
+            });
+            offset = offset.checked_add(chars).ok_or(Error::Corrupt)?;
+            position = position.checked_add(size).ok_or(Error::Corrupt)?;
+        }
+        if !bytes.is_empty() || position > end - size {
+            return Err(Error::Corrupt);
+        }
+        Ok(Self { entries })
+    }
+
+    /**
+        Number of indexed blocks
+    */
+    pub(crate) fn blocks(&self) -> u64 {
+        self.entries.len() as u64
+    }
+
+    /**
+        Number of characters in all blocks
+    */
+    pub(crate) fn chars(&self) -> u64 {
+        self.entries.last().map_or(0, |e| e.offset + e.chars)
+    }
+
+    /**
+        Decodes `len` characters starting at `offset` from blocks covering them
+
+        Characters past the end of data are not returned.
+    */
+    pub(crate) fn read_range<R: Read + Seek>(
+        &self,
+        r: &mut R,
+        kind: ModelKind,
+        block_size: u64,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let end = offset.saturating_add(len);
+        let first = self.entries.partition_point(|e| e.offset + e.chars <= offset);
+        let mut res = Vec::new();
+        for entry in self.entries[first..].iter().take_while(|e| e.offset < end) {
+            r.seek(SeekFrom::Start(entry.position))?;
+            let code = read_code(r)?.ok_or(Error::Corrupt)?;
+            let block = decode_block(&code, kind, block_size)?;
+            if block.len() as u64 != entry.chars {
+                return Err(Error::Corrupt);
+            }
+            let from = offset.saturating_sub(entry.offset) as usize;
+            let to = (end - entry.offset).min(entry.chars) as usize;
+            res.extend_from_slice(&block[from..to]);
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,110 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::error::Error;
+use crate::header::Header;
+use crate::index::BlockIndex;
+use crate::varint::read_varint;
+
+/**
+    Metadata of compressed data, read without decoding it
+
+    Single stream stores its length after the header, unless it is ended by
+    an end symbol, and the checksum of all characters at its end. Blocks
+    store their length each, so their sum is read from the index when there
+    is one, or from every block otherwise. Checksums of blocks are checked
+    when they are decoded and there is no checksum of all characters.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContainerInfo {
+    /// Header of data
+    pub header: Header,
+    /// Number of characters, None if data is ended by end symbol
+    pub chars: Option<u64>,
+    /// Size of compressed data in bytes, including header
+    pub size: u64,
+    /// Stored CRC-32C of all characters, None for blocks
+    pub checksum: Option<u32>,
+    /// Largest number of characters in one block, None for single stream
+    pub block_size: Option<u64>,
+    /// Number of blocks, None for single stream
+    pub blocks: Option<u64>,
+}
+
+impl ContainerInfo {
+    /**
+        Reads metadata of compressed data starting at the current position of `r` and ending at its end
+
+        Stored checksum is returned as it is, without being verified.
+    */
+    pub fn read_from<R: Read + Seek>(r: &mut R) -> Result<Self, Error> {
+        let start = r.stream_position()?;
+        let header = Header::read_from(r)?;
+        let end = r.seek(SeekFrom::End(0))?;
+        r.seek(SeekFrom::Start(start + Header::SIZE as u64))?;
+        let mut info = Self {
+            header,
+            chars: None,
+            size: end - start,
+            checksum: None,
+            block_size: None,
+            blocks: None,
+        };
+        if header.flags & Header::BLOCKS != 0 {
+            let block_size = read_varint(r)?;
+            if block_size == 0 {
+                return Err(Error::Corrupt);
+            }
+            info.block_size = Some(block_size);
+            let (blocks, chars) = if header.flags & Header::INDEX != 0 {
+                let position = r.stream_position()?;
+                let index = BlockIndex::read_from(r, position)?;
+                (index.blocks(), index.chars())
+            } else {
+                Self::count_blocks(r, block_size)?
+            };
+            info.blocks = Some(blocks);
+            info.chars = Some(chars);
+            return Ok(info);
+        }
+        if header.flags & Header::END_SYMBOL == 0 {
+            info.chars = Some(read_varint(r)?);
+        }
+        let position = r.stream_position()?;
+        if end < position + 4 {
+            return Err(Error::Truncated);
+        }
+        r.seek(SeekFrom::Start(end - 4))?;
+        let mut checksum = [0; 4];
+        r.read_exact(&mut checksum)?;
+        info.checksum = Some(u32::from_be_bytes(checksum));
+        Ok(info)
+    }
+
+    /**
+        Returns number of blocks and characters in them, reading only their lengths
+    */
+    fn count_blocks<R: Read + Seek>(r: &mut R, block_size: u64) -> Result<(u64, u64), Error> {
+        let (mut blocks, mut chars) = (0, 0_u64);
+        loop {
+            let size = read_varint(r)?;
+            if size == 0 {
+                return Ok((blocks, chars));
+            }
+            let position = r.stream_position()?;
+            let block = read_varint(&mut r.by_ref().take(size))?;
+            if block > block_size {
+                return Err(Error::Corrupt);
+            }
+            r.seek(SeekFrom::Start(position.checked_add(size).ok_or(Error::Corrupt)?))?;
+            blocks += 1;
+            chars = chars.checked_add(block).ok_or(Error::Corrupt)?;
+        }
+    }
+
+    /**
+        Ratio of compressed size to number of characters, None if it is unknown or zero
+    */
+    pub fn ratio(&self) -> Option<f64> {
+        self.chars.filter(|&chars| chars > 0).map(|chars| self.size as f64 / chars as f64)
+    }
+}
@@ -0,0 +1,236 @@
+/*!
+    Adaptive arithmetic compression with scaling
+
+    Marek Bauer 2020
+
+    Data is coded with an adaptive model: probabilities of characters are
+    recomputed from already coded characters, so no table has to be stored
+    next to the compressed data. By default an order-0 model is used; order-k
+    context, PPM and context mixing models can be selected with
+    `compress_with` and `ModelKind`.
+
+    ```
+    let data = b"abracadabra";
+    let compressed = arithmetic_coder::compress(data);
+    let decompressed = arithmetic_coder::decompress(&compressed).unwrap();
+    assert_eq!(decompressed, data);
+    ```
+
+    Large inputs can be coded in constant memory with `ArithmeticEncoder` and
+    `ArithmeticDecoder`, which implement `std::io::Write` and `std::io::Read`.
+    Both are generic over the `Model` providing probabilities of characters.
+    `compress` additionally writes a `Header` identifying the format and the
+    `ModelKind` in front of the coded data, which streaming users write and
+    read with `Header::write_to` and `Header::read_from`. Input of unknown
+    length can be coded with `ArithmeticEncoder::with_end_symbol`, which ends
+    the stream with an end symbol instead of storing its length; such data is
+    marked with `Header::END_SYMBOL`.
+
+    `BlockEncoder` and `BlockDecoder` split data into blocks coded
+    independently, each with its own length and checksum, so damage is
+    confined to one block. `compress_blocks` does the same for data in memory.
+    Blocks finished with `BlockEncoder::finish_with_index` are followed by an
+    index, which lets `decompress_range` decode only blocks covering a range.
+    `ContainerInfo` describes compressed data without decoding it.
+    `copy_with_progress` feeds coders while reporting to a `Progress`.
+
+    Bitwise models can drive the coder one bit at a time through
+    `BinaryEncoder` and `BinaryDecoder`.
+*/
+
+mod binary;
+mod block;
+mod context;
+mod crc;
+mod decoder;
+mod encoder;
+mod entropy;
+mod error;
+mod fenwick;
+mod header;
+mod index;
+mod info;
+mod mixing;
+mod model;
+mod ppm;
+mod progress;
+mod probabilities;
+mod range;
+mod varint;
+
+use std::io::{self, Read, Seek, Write};
+
+pub use binary::{BinaryDecoder, BinaryEncoder, BitProbability, PROBABILITY_ONE};
+pub use block::{BlockDecoder, BlockEncoder, DEFAULT_BLOCK_SIZE};
+pub use context::ContextModel;
+pub use decoder::ArithmeticDecoder;
+pub use encoder::ArithmeticEncoder;
+pub use error::Error;
+pub use header::Header;
+pub use info::ContainerInfo;
+pub use mixing::MixingModel;
+pub use model::{Model, ModelKind, ESCAPE, MAX_TOTAL};
+pub use ppm::PpmModel;
+pub use probabilities::Probabilities;
+pub use progress::{copy_with_progress, Progress};
+
+/**
+    Compresses `data` with the default model and returns coded bytes
+
+    Empty data is compressed to the header followed by zero length, one
+    byte ending the code and zero checksum, whatever the model:
+
+    ```
+    use arithmetic_coder::{compress, decompress, Header};
+
+    let compressed = compress(b"");
+    assert_eq!(compressed[Header::SIZE..], [0, 0x40, 0, 0, 0, 0]);
+    assert_eq!(decompress(&compressed).unwrap(), b"");
+    ```
+*/
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    compress_with(data, ModelKind::default())
+}
+
+/**
+    Compresses `data` with model of given kind and returns coded bytes
+
+    Panics if order of `kind` is greater than `MAX_ORDER` of its model.
+*/
+pub fn compress_with(data: &[u8], kind: ModelKind) -> Vec<u8> {
+    let mut res = Vec::new();
+    Header::new(kind).write_to(&mut res).expect("writing to vector can not fail");
+    res.extend(block::encode_block(data, kind));
+    res
+}
+
+/**
+    Compresses `data` in independently coded blocks of `block_size` characters
+
+    Panics if `block_size` is zero or order of `kind` is greater than
+    `MAX_ORDER` of its model.
+*/
+pub fn compress_blocks(data: &[u8], kind: ModelKind, block_size: usize) -> Vec<u8> {
+    let mut res = Vec::new();
+    let header = Header {
+        flags: Header::BLOCKS,
+        ..Header::new(kind)
+    };
+    header.write_to(&mut res).expect("writing to vector can not fail");
+    let mut encoder = BlockEncoder::new(res, kind, block_size).expect("writing to vector can not fail");
+    encoder.write_all(data).expect("writing to vector can not fail");
+    encoder.finish().expect("writing to vector can not fail")
+}
+
+/**
+    Reads header from `input` and returns decoder of data following it
+*/
+fn open<'a, R: Read + 'a>(mut input: R) -> Result<Box<dyn Read + 'a>, Error> {
+    let header = Header::read_from(&mut input)?;
+    Ok(if header.flags & Header::BLOCKS != 0 {
+        Box::new(BlockDecoder::new(input, header.model)?)
+    } else if header.flags & Header::END_SYMBOL != 0 {
+        Box::new(ArithmeticDecoder::with_end_symbol(input, header.model.build())?)
+    } else {
+        Box::new(ArithmeticDecoder::with_model(input, header.model.build())?)
+    })
+}
+
+/**
+    Decompresses bytes returned by `compress`, `compress_with` or `compress_blocks`
+*/
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut res = Vec::new();
+    open(data)?.read_to_end(&mut res)?;
+    Ok(res)
+}
+
+/**
+    Decompresses `len` characters starting at `offset` from compressed data in `input`
+
+    Compressed data has to start at the current position of `input`. If it
+    has an index of blocks, only blocks covering the range are decoded,
+    otherwise everything up to the end of the range is. Characters past the
+    end of data are not returned.
+
+    ```
+    use std::io::{Cursor, Write};
+    use arithmetic_coder::{BlockEncoder, Header, ModelKind};
+
+    let kind = ModelKind::default();
+    let mut compressed = Vec::new();
+    Header { flags: Header::BLOCKS | Header::INDEX, ..Header::new(kind) }
+        .write_to(&mut compressed).unwrap();
+    let mut encoder = BlockEncoder::new(compressed, kind, 4).unwrap();
+    encoder.write_all(b"abracadabra").unwrap();
+    let compressed = encoder.finish_with_index().unwrap();
+
+    let range = arithmetic_coder::decompress_range(Cursor::new(compressed), 5, 4).unwrap();
+    assert_eq!(range, b"adab");
+    ```
+*/
+pub fn decompress_range<R: Read + Seek>(mut input: R, offset: u64, len: u64) -> Result<Vec<u8>, Error> {
+    let start = input.stream_position()?;
+    let header = Header::read_from(&mut input)?;
+    let mut res = Vec::new();
+    if header.flags & Header::INDEX == 0 {
+        input.seek(io::SeekFrom::Start(start))?;
+        let mut decoder = open(input)?;
+        io::copy(&mut (&mut decoder).take(offset), &mut io::sink())?;
+        decoder.take(len).read_to_end(&mut res)?;
+        return Ok(res);
+    }
+    let block_size = varint::read_varint(&mut input)?;
+    if block_size == 0 {
+        return Err(Error::Corrupt);
+    }
+    let blocks = input.stream_position()?;
+    let index = index::BlockIndex::read_from(&mut input, blocks)?;
+    index.read_range(&mut input, header.model, block_size, offset, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+        Every kind of model, including lowest and highest orders
+    */
+    fn kinds() -> Vec<ModelKind> {
+        let mut kinds = vec![ModelKind::Adaptive, ModelKind::Mixing];
+        for order in 0..=ContextModel::MAX_ORDER {
+            kinds.push(ModelKind::Context(order));
+        }
+        for order in 0..=PpmModel::MAX_ORDER {
+            kinds.push(ModelKind::Ppm(order));
+        }
+        kinds
+    }
+
+    /**
+        Text with repeated words, long enough for models to rescale their frequencies
+    */
+    fn text() -> Vec<u8> {
+        let words = ["abra", "cadabra", " ", "alakazam", "\n", "hocus", "pocus", ", "];
+        let mut seed = 12345_u32;
+        let mut res = Vec::new();
+        while res.len() < 20000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            res.extend_from_slice(words[(seed >> 16) as usize % words.len()].as_bytes());
+        }
+        res
+    }
+
+    #[test]
+    fn round_trip_every_model() {
+        let text = text();
+        let all: Vec<u8> = (0..=255).collect();
+        for kind in kinds() {
+            for data in [&b""[..], b"a", &all, &text] {
+                let compressed = compress_with(data, kind);
+                assert_eq!(decompress(&compressed).unwrap(), data, "{} of {} bytes", kind, data.len());
+            }
+        }
+    }
+}
+
@@ -4,407 +4,1092 @@
     Marek Bauer 2020
 */
 
-use std::fs::File;
-use std::path::Path;
-use std::io::{Write, Read};
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use arithmetic_coder::{ArithmeticDecoder, ArithmeticEncoder, BlockDecoder, BlockEncoder, ContainerInfo, ContextModel, copy_with_progress, decompress_range, DEFAULT_BLOCK_SIZE, Error, Header, ModelKind, PpmModel, Progress};
+
+/// Extension of compressed files
+const EXTENSION: &str = ".ac";
+
+const HELP: &str = "\
+Usage: coder <COMMAND> [OPTIONS] [FILE]
+       coder compress|decompress|test [OPTIONS] [FILE...]
+
+Commands:
+  compress     Compress every FILE to FILE.ac
+  decompress   Decompress every FILE.ac to FILE
+  test         Check that every FILE decompresses and matches its checksum
+  info         Show sizes, model and checksum of FILE without decompressing it
+  bench        Compress and decompress FILE in memory and report speed
+
+Without FILE or with FILE -, standard input is read and the result is
+written to standard output.
+
+Options:
+  -o, --output <PATH>        Write result to PATH, - for standard output,
+                             directory for several files or with -r
+  -c, --stdout               Write result to standard output
+  -f, --force                Overwrite existing files, write compressed data to a terminal
+  -k, --keep                 Keep input file after compressing or decompressing
+  -r, --recursive            Compress or decompress files in directories, keeping their tree
+  -q, --quiet                Print only errors
+  -v, --verbose              Print statistics
+      --order <0-8>          Compress with order-k context model
+      --ppm <0-8>            Compress with order-k PPM model
+      --mixing               Compress with context mixing model
+      --block-size <BYTES>   Compress in independent blocks of BYTES
+      --index                Append index of blocks for --range
+      --threads <N>          Code up to N blocks at once, compress in blocks
+      --range <OFFSET>:<LEN> Decompress only LEN bytes starting at OFFSET
+      --json                 Print info as JSON
+      --stats-format <FMT>   Print statistics of compress or decompress as json or csv,
+                             to standard error when result goes to standard output
+  -h, --help                 Print this help
+  -V, --version              Print version";
 
 /**
-    Struct representing probabilities of characters in file
+    Progress bar redrawn in place on standard error
+
+    Input of unknown length shows number of bytes done instead of a bar.
 */
-#[derive(Debug)]
-struct Probabilities {
-    sum: u64,
-    pro: Vec<u64>,
-    temp: Vec<u64>,
-    cycle: u64,
+struct Bar {
+    shown: Option<u64>, //last shown percent or MiB
 }
 
-impl Probabilities {
-    const PRECISION: u64 = 1048576 * 1024; //Select the denominator of probabilities
-    const CYCLE: u64 = 64; //Select length of cycle
+impl Bar {
+    const WIDTH: u64 = 50;
 
-    fn new() -> Self {
-        let mut pro = vec![0; 257];
-        for i in 0..257_u64 {
-            pro[i as usize] = i;
+    /**
+        Creates bar if progress should be shown, which needs standard error to be a terminal
+    */
+    fn new(options: &Options) -> Option<Self> {
+        (options.verbosity != Verbosity::Quiet && io::stderr().is_terminal()).then_some(Bar { shown: None })
+    }
+}
+
+impl Progress for Bar {
+    fn update(&mut self, done: u64, total: Option<u64>) {
+        let shown = match total {
+            Some(0) => 100,
+            Some(total) => done * 100 / total,
+            None => done >> 20,
+        };
+        if self.shown == Some(shown) {
+            return;
+        }
+        self.shown = Some(shown);
+        match total {
+            Some(_) => {
+                let filled = (shown * Self::WIDTH / 100) as usize;
+                let empty = Self::WIDTH as usize - filled;
+                eprint!("\r|{}{}| {}%", "█".repeat(filled), " ".repeat(empty), shown);
+            }
+            None => eprint!("\r{} MiB", shown),
         }
-        Probabilities {
-            sum: 256,
-            temp: vec![0; 256],
-            pro,
-            cycle: 0,
+    }
+
+    fn finish(&mut self) {
+        if self.shown.take().is_some() {
+            eprintln!();
         }
     }
+}
 
+impl Drop for Bar {
+    //End the line of unfinished bar, so that errors start on a new one
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+struct Statistics {
+    chars: u64,
+    size: u64,
+    entropy: f32,
+}
+
+impl Statistics {
     /**
-    Add char to probability computations
+        Compressed size divided by original size, None for empty input
     */
-    fn add(&mut self, to_add: usize) {
-        self.temp[to_add] += 1;
-        self.cycle += 1;
-        if self.cycle >= Self::CYCLE {
-            self.cycle = 0;
-            self.update_probabilities();
-        }
+    fn ratio(&self) -> Option<f64> {
+        (self.chars > 0).then(|| self.size as f64 / self.chars as f64)
     }
 
     /**
-    Update probabilities
+        Bits of compressed data per original byte, None for empty input
     */
-    fn update_probabilities(&mut self) {
-        let sum = self.temp.iter().fold(0_u64, |a, b| a + *b);
-        let c = (Self::PRECISION - 256) as f64 / sum as f64;
-        let mut t: Vec<u64> = vec![];
-        //Compute probabilities to add up to PRECISION
-        for v in self.temp.iter() {
-            t.push((*v as f64 * c).floor() as u64 + 1);
-        }
-        let all = t.iter().fold(0_u64, |a, b| a + *b);
-        let deficit = Self::PRECISION - all;
-        //Rest add to first elements
-        for i in 0_usize..deficit as usize {
-            t[i] += 1;
-        }
-        let mut temp = 0;
-        //Update pro array
-        self.pro = vec![0; 257];
-        for i in 1_usize..257 {
-            temp += t[i - 1];
-            self.pro[i] = temp;
-        }
-        assert_eq!(self.pro[256], Self::PRECISION);
-        self.sum = Self::PRECISION;
+    fn bits_per_byte(&self) -> Option<f64> {
+        self.ratio().map(|ratio| ratio * 8.0)
     }
 }
 
 /**
-    Struct representing coded sequence
+    Millions of `bytes` per second done in `seconds`, None if no time was measured
 */
-#[derive(Debug)]
-struct Code {
-    data: Vec<u8>,
-    write_index: usize,
-    read_index: usize,
-    entropy: f32,
-    chars: usize, //number of character encoded
+fn throughput(bytes: u64, seconds: f64) -> Option<f64> {
+    (seconds > 0.0).then(|| bytes as f64 / seconds / 1e6)
 }
 
-impl Code {
-    const BIN: [u8; 8] = [128, 64, 32, 16, 8, 4, 2, 1];
+fn print_compression_statistics(statistics: &Statistics){
+    eprintln!("Size before compression: {}B", statistics.chars);
+    eprintln!("Size after compression: {}B", statistics.size);
+    if let Some(ratio) = statistics.ratio() {
+        eprintln!("Compression ratio: {}%", ratio * 100.0);
+    }
+    eprintln!("Entropy: {}", statistics.entropy);
+}
 
-    fn new() -> Self {
-        Self {
-            data: Vec::new(),
-            write_index: 0,
-            read_index: 0,
-            entropy: 0.0,
-            chars: 0,
+/**
+    Machine-readable format of statistics
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatsFormat {
+    Json,
+    Csv,
+}
+
+/// Line naming columns of statistics formatted as CSV
+const CSV_HEADER: &str = "file,operation,original_size,compressed_size,ratio,entropy,bits_per_byte,elapsed,throughput";
+
+/**
+    Formats statistics of `command` run on `file` taking `seconds` in given format
+
+    CSV rows follow `CSV_HEADER`. Ratio, bits per byte and throughput are
+    null in JSON and empty in CSV when they are not defined.
+*/
+fn format_statistics(format: StatsFormat, command: Command, file: &str, statistics: &Statistics, seconds: f64) -> String {
+    let operation = if command == Command::Compress { "compress" } else { "decompress" };
+    let throughput = throughput(statistics.chars, seconds);
+    match format {
+        StatsFormat::Json => format!(
+            "{{\"file\":{},\"operation\":\"{}\",\"original_size\":{},\"compressed_size\":{},\"ratio\":{},\"entropy\":{},\"bits_per_byte\":{},\"elapsed\":{},\"throughput\":{}}}",
+            json_string(file),
+            operation,
+            statistics.chars,
+            statistics.size,
+            json_option(statistics.ratio()),
+            statistics.entropy,
+            json_option(statistics.bits_per_byte()),
+            seconds,
+            json_option(throughput),
+        ),
+        StatsFormat::Csv => {
+            let field = |value: Option<f64>| value.map_or_else(String::new, |value| value.to_string());
+            format!(
+                "{},{},{},{},{},{},{},{},{}",
+                csv_field(file),
+                operation,
+                statistics.chars,
+                statistics.size,
+                field(statistics.ratio()),
+                statistics.entropy,
+                field(statistics.bits_per_byte()),
+                seconds,
+                field(throughput),
+            )
+        }
+    }
+}
+
+/**
+    Source of input, file or standard input for path `-`
+*/
+enum Input {
+    File(File),
+    Stdin(io::Stdin),
+}
+
+impl Input {
+    fn open(path: &str) -> io::Result<Self> {
+        if path == "-" {
+            Ok(Input::Stdin(io::stdin()))
+        } else {
+            File::open(path).map(Input::File)
         }
     }
 
     /**
-        Add bit to code
+        Length of input, None if it is not a regular file
     */
-    fn add_bit(&mut self, c: bool) {
-        let sector = self.write_index % 8;
-        if sector == 0 {
-            self.data.push(0);
+    fn len(&self) -> io::Result<Option<u64>> {
+        match self {
+            Input::File(f) => {
+                let metadata = f.metadata()?;
+                Ok(if metadata.is_file() { Some(metadata.len()) } else { None })
+            }
+            Input::Stdin(_) => Ok(None),
         }
-        if c {
-            let block = self.write_index / 8;
-            self.data[block] |= Self::BIN[sector]
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(f) => f.read(buf),
+            Input::Stdin(s) => s.read(buf),
         }
-        self.write_index += 1;
     }
+}
 
-    /**
-        Read one bit of your code
-    */
-    fn get_bit(&self, i: usize) -> bool {
-        return !self.data[i / 8] & Self::BIN[i % 8] == 0;
+/**
+    Destination of output, file or standard output for path `-`
+*/
+enum Output {
+    File(File),
+    Stdout(io::Stdout),
+}
+
+impl Output {
+    fn create(path: &str) -> io::Result<Self> {
+        if path == "-" {
+            Ok(Output::Stdout(io::stdout()))
+        } else {
+            File::create(path).map(Output::File)
+        }
     }
 
     /**
-        Read one bit and shift read_index
+        Makes sure written data reached the disk
     */
-    fn get_bit_and_shift(&mut self) -> bool {
-        if self.read_index < (self.data.len() * 8) {
-            self.read_index += 1;
-            return self.get_bit((self.read_index - 1) as usize);
+    fn sync(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(f) => f.sync_all(),
+            Output::Stdout(s) => s.flush(),
         }
-        false
     }
+}
 
-    fn print_bar(p: u32){
-        print!("|");
-        for _i in 0..p{
-            print!("█");
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::File(f) => f.write(buf),
+            Output::Stdout(s) => s.write(buf),
         }
-        for _i in p..100{
-            print!(" ");
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(f) => f.flush(),
+            Output::Stdout(s) => s.flush(),
         }
-        println!("| {}%", p);
     }
+}
 
-    fn write_to_file<X>(&self, path: X) -> Result<(), String> where X: AsRef<Path> {
-        let mut file;
-        match File::create(path){
-            Ok(f) => file = f,
-            Err(_e) => return Err("Unable to open file".parse().unwrap())
+/**
+    Counts bytes passing through the inner reader or writer
+*/
+struct Counter<T> {
+    inner: T,
+    count: u64,
+}
+
+impl<T> Counter<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<T: Read> Read for Counter<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for Counter<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/**
+    Copies `chars` characters or everything if their number is not known
+    from `input` to `output`, drawing progress bar if it should be shown
+*/
+fn copy<R: Read, W: Write>(input: &mut R, output: &mut W, chars: Option<u64>, options: &Options) -> io::Result<u64> {
+    match Bar::new(options) {
+        Some(mut bar) => copy_with_progress(input, output, chars, &mut bar),
+        None => copy_with_progress(input, output, chars, &mut |_, _| {}),
+    }
+}
+
+/**
+    Encodes `chars` characters, or everything if their number is not known,
+    from `input` to `output` in constant memory
+
+    Input of unknown length, such as a pipe, is read until its end and coded
+    with an end symbol. With block size, threads or index input is coded in
+    independent blocks, by up to `threads` threads at once. Number of
+    threads never changes the output.
+*/
+fn encode<R: Read, W: Write>(input: &mut R, chars: Option<u64>, output: &mut W, options: &Options) -> io::Result<Statistics> {
+    let kind = options.kind;
+    let block_size = match (options.block_size, options.threads, options.index) {
+        (None, None, false) => None,
+        (block_size, _, _) => Some(block_size.unwrap_or(DEFAULT_BLOCK_SIZE)),
+    };
+    let mut output = BufWriter::new(Counter::new(output));
+    let mut header = Header::new(kind);
+    if block_size.is_some() {
+        header.flags |= Header::BLOCKS;
+        if options.index {
+            header.flags |= Header::INDEX;
         }
-        let s0 = (self.chars % 256) as u8;
-        let s1 = (self.chars / 256 % 256) as u8;
-        let s2 = (self.chars / 65536 % 256) as u8;
-        let s3 = (self.chars / 16777216 % 256) as u8;
-        //Save data
-        match file.write(self.data.as_ref()){
-            Err(_e) => return Err("Unable to save file".parse().unwrap()),
-            _ => {}
+    } else if chars.is_none() {
+        header.flags |= Header::END_SYMBOL;
+    }
+    header.write_to(&mut output)?;
+    let (chars, entropy, output) = match block_size {
+        Some(block_size) => {
+            let mut encoder = BlockEncoder::with_threads(output, kind, block_size, options.threads.unwrap_or(1))?;
+            let chars = copy(input, &mut encoder, chars, options)?;
+            let entropy = encoder.entropy();
+            let output = if options.index {
+                encoder.finish_with_index()?
+            } else {
+                encoder.finish()?
+            };
+            (chars, entropy, output)
         }
-        //Save number of encoded characters
-        match file.write([s3, s2, s1, s0].as_ref()){
-            Err(_e) => return Err("Unable to save file".parse().unwrap()),
-            _ => {}
+        None => {
+            let mut encoder = match chars {
+                Some(chars) => ArithmeticEncoder::with_model(output, chars, kind.build())?,
+                None => ArithmeticEncoder::with_end_symbol(output, kind.build()),
+            };
+            let chars = copy(input, &mut encoder, chars, options)?;
+            (chars, encoder.entropy(), encoder.finish()?)
         }
-        match file.sync_all(){
-            Err(_e) => return Err("Unable to save file".parse().unwrap()),
-            _ => {}
+    };
+    let output = output.into_inner().map_err(|e| e.into_error())?;
+    Ok(Statistics {
+        chars,
+        size: output.count,
+        entropy,
+    })
+}
+
+/**
+    Decodes characters from `input` to `output` in constant memory
+
+    Blocks are decoded by up to `threads` threads at once.
+*/
+fn decode<R: Read, W: Write>(input: &mut R, output: &mut W, options: &Options) -> Result<Statistics, Error> {
+    let mut counter = Counter::new(input);
+    let mut input = BufReader::new(&mut counter);
+    let header = Header::read_from(&mut input)?;
+    let mut output = BufWriter::new(output);
+    let (chars, entropy) = if header.flags & Header::BLOCKS != 0 {
+        let mut decoder = BlockDecoder::with_threads(input, header.model, options.threads.unwrap_or(1))?;
+        (copy(&mut decoder, &mut output, None, options)?, decoder.entropy())
+    } else {
+        let mut decoder = if header.flags & Header::END_SYMBOL != 0 {
+            ArithmeticDecoder::with_end_symbol(input, header.model.build())?
+        } else {
+            ArithmeticDecoder::with_model(input, header.model.build())?
+        };
+        let chars = decoder.chars();
+        (copy(&mut decoder, &mut output, chars, options)?, decoder.entropy())
+    };
+    output.flush()?;
+    Ok(Statistics {
+        chars,
+        size: counter.count,
+        entropy,
+    })
+}
+
+/**
+    Decodes `len` characters starting at `offset` from `input` to `output`
+
+    Standard input can not seek, so it is read into memory first.
+*/
+fn decode_range<W: Write>(input: Input, output: &mut W, offset: u64, len: u64) -> Result<u64, Error> {
+    let range = match input {
+        Input::File(f) => decompress_range(BufReader::new(f), offset, len)?,
+        Input::Stdin(mut s) => {
+            let mut data = Vec::new();
+            s.read_to_end(&mut data)?;
+            decompress_range(Cursor::new(data), offset, len)?
         }
-        Ok(())
-    }
+    };
+    output.write_all(&range)?;
+    Ok(range.len() as u64)
+}
+
+/**
+    Input file and its path relative to output directory
+*/
+struct Job {
+    file: String,
+    relative: PathBuf,
+}
+
+/**
+    Lists files `command` runs on, walking directories if options are recursive
 
-    fn read_from_file<X>(path: X) -> Result<Self, String> where X: AsRef<Path> {
-        let mut file;
-        match File::open(path){
-            Ok(f) => file = f,
-            Err(_e) => return Err("Unable to open file".parse().unwrap())
+    Files are taken from directories in order of their names, only those
+    `command` applies to: compression skips files already having the
+    extension and decompression takes only them. Symbolic links found in
+    directories are skipped. Paths of found files relative to output
+    directory start with the name of the directory given.
+*/
+fn collect_files(command: Command, options: &Options) -> Result<Vec<Job>, String> {
+    let files = if options.files.is_empty() { vec![String::from("-")] } else { options.files.clone() };
+    let mut jobs = Vec::new();
+    for file in files {
+        let path = Path::new(&file);
+        if file != "-" && path.is_dir() {
+            if !options.recursive {
+                return Err(format!("{}: is a directory, use -r to code files in it", file));
+            }
+            let relative = path.file_name().map(PathBuf::from).unwrap_or_default();
+            walk(command, path, &relative, &mut jobs)?;
+        } else {
+            let relative = path.file_name().map_or_else(|| PathBuf::from(&file), PathBuf::from);
+            jobs.push(Job { file, relative });
         }
-        let mut data = vec![];
-        match file.read_to_end(data.as_mut()) {
-            Err(_e) => return Err("Unable to read file".parse().unwrap()),
-            _ => {}
+    }
+    Ok(jobs)
+}
+
+/**
+    Adds files in `dir` and its subdirectories to `jobs`
+*/
+fn walk(command: Command, dir: &Path, relative: &Path, jobs: &mut Vec<Job>) -> Result<(), String> {
+    let error = |e: io::Error| format!("{}: {}", dir.display(), e);
+    let mut entries = fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()).map_err(error)?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let kind = entry.file_type().map_err(error)?;
+        let path = entry.path();
+        let relative = relative.join(entry.file_name());
+        if kind.is_dir() {
+            walk(command, &path, &relative, jobs)?;
+        } else if kind.is_file() {
+            let compressed = entry.file_name().to_string_lossy().ends_with(EXTENSION);
+            if compressed == (command == Command::Decompress) {
+                jobs.push(Job {
+                    file: path.to_string_lossy().into_owned(),
+                    relative,
+                });
+            }
         }
-        let mut chars = 0;
-        //Read number of encoded characters
-        for i in 0_usize..=3 {
-            let x = data.pop().expect("Wrong file");
-            chars += x as usize * 256_u32.pow(i as u32) as usize;
+    }
+    Ok(())
+}
+
+/**
+    Path of output of `command` run on `job`, `-` for standard output
+
+    Output of one of `many` files goes next to it, or to the same path
+    relative to output directory if one is given.
+*/
+fn output_path(command: Command, job: &Job, many: bool, options: &Options) -> Result<String, String> {
+    match &options.output {
+        Some(output) if output == "-" || !many => return Ok(output.clone()),
+        _ => {}
+    }
+    if options.stdout || job.file == "-" {
+        return Ok(String::from("-"));
+    }
+    let path = match &options.output {
+        Some(directory) => Path::new(directory).join(&job.relative).to_string_lossy().into_owned(),
+        None => job.file.clone(),
+    };
+    match command {
+        Command::Compress => Ok(format!("{}{}", path, EXTENSION)),
+        _ => match path.strip_suffix(EXTENSION) {
+            Some(stem) if !stem.is_empty() && !stem.ends_with(std::path::is_separator) => Ok(stem.to_string()),
+            _ => Err(format!("{}: unknown suffix, use -o to name output", job.file)),
+        },
+    }
+}
+
+/**
+    Whether paths `a` and `b` name the same existing file
+*/
+fn same_file(a: &str, b: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
         }
-        Ok(Self {
-            write_index: (data.len() * 8),
-            data,
-            chars,
-            entropy: 0.0,
-            read_index: 0,
-        })
     }
+    #[cfg(not(unix))]
+    {
+        matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+    }
+}
 
-    /**
-        Returns one encoded char by value
-    */
-    fn get_code(low: u32, high: u32, val: u32, prob: &Probabilities) -> u8 {
-        let range = high as u64 - low as u64 + 1;
-        for i in 1 as u8..=255 {
-            if (val as u64) <= (low as u64) + (range * prob.pro[i as usize] as u64) / prob.sum as u64 - 1 {
-                return i - 1;
+/**
+    Compresses or decompresses `file` to `to` as told by `command`
+
+    Output is created only after input was opened, and removed again if
+    coding fails. Input is removed after success unless it is kept or only
+    a range of it was decompressed. Returns statistics and seconds spent
+    coding.
+*/
+fn code_file(command: Command, file: &str, to: &str, options: &Options) -> Result<(Statistics, f64), String> {
+    let mut input = Input::open(file).map_err(|e| format!("{}: {}", file, e))?;
+    //Length has to be known before output is created, which may truncate it
+    let chars = input.len().map_err(|e| format!("{}: {}", file, e))?;
+    if file != "-" && to != "-" && same_file(file, to) {
+        return Err(format!("{}: output would overwrite input", to));
+    }
+    if to == "-" {
+        if command == Command::Compress && !options.force && io::stdout().is_terminal() {
+            return Err(String::from("compressed data not written to a terminal, use -f to force"));
+        }
+    } else if !options.force && Path::new(to).exists() {
+        return Err(format!("{}: already exists, use -f to overwrite", to));
+    }
+    let mut output = Output::create(to).map_err(|e| format!("{}: {}", to, e))?;
+    if options.verbosity == Verbosity::Verbose {
+        let action = if command == Command::Compress { "Compressing" } else { "Decompressing" };
+        eprintln!("{} {} to {}", action, file, to);
+    }
+    let start = Instant::now();
+    let statistics = match command {
+        Command::Compress => encode(&mut input, chars, &mut output, options).map_err(Error::from),
+        _ => match options.range {
+            Some((offset, len)) => decode_range(input, &mut output, offset, len).map(|chars| Statistics {
+                chars,
+                size: 0,
+                entropy: 0.0,
+            }),
+            None => decode(&mut input, &mut output, options),
+        },
+    };
+    let statistics = statistics.and_then(|s| output.sync().map(|_| s).map_err(Error::from));
+    match statistics {
+        Ok(statistics) => {
+            let seconds = start.elapsed().as_secs_f64();
+            if options.verbosity == Verbosity::Verbose && options.range.is_none() {
+                print_compression_statistics(&statistics);
             }
+            //Range is only a part of input, which has to stay
+            if file != "-" && to != "-" && !options.keep && options.range.is_none() {
+                fs::remove_file(file).map_err(|e| format!("{}: {}", file, e))?;
+            }
+            Ok((statistics, seconds))
         }
-        255
-    }
-
-    fn decode(&mut self) -> Vec<u8> {
-        println!("Decoding...");
-        let mut prob = Probabilities::new();
-        let mut res = Vec::new();
-        let mut high = 0xFFFFFFFF_u32;
-        let mut low = 0_u32;
-        let mut value = 0_u32;
-        let mut percent = 0;
-        for _ in 0..32 {
-            value <<= 1;
-            if self.get_bit_and_shift() {
-                value += 1;
+        Err(e) => {
+            if to != "-" {
+                let _ = fs::remove_file(to);
             }
+            Err(format!("{}: {}", file, e))
         }
-        loop {
-            let range = high as u64 - (low as u64) + 1;
-            let c = Self::get_code(low, high, value, &prob);
-            res.push(c);
-            high = (low as u64 + (range * prob.pro[c as usize + 1] as u64) / prob.sum as u64 - 1) as u32;
-            low = (low as u64 + (range * prob.pro[c as usize] as u64) / prob.sum as u64) as u32;
-            if res.len()*100 / self.chars > percent{
-                Self::print_bar(percent as u32);
-                percent += 1;
+    }
+}
+
+/**
+    Options for coding one of many files
+
+    Progress and messages of every file would only hide their results.
+*/
+fn quiet(options: &Options) -> Options {
+    Options {
+        verbosity: Verbosity::Quiet,
+        ..options.clone()
+    }
+}
+
+/**
+    Compresses or decompresses all files given by options as told by `command`
+
+    Single file is coded as told by options. Several files, or files found
+    in directories, are each coded next to themselves or into output
+    directory, printing OK or FAILED for each of them followed by total
+    sizes, unless statistics are printed instead. Coding continues after
+    a file fails.
+*/
+fn code_files(command: Command, options: &Options) -> Result<(), String> {
+    let jobs = collect_files(command, options)?;
+    let many = options.recursive || jobs.len() > 1;
+    if many && options.range.is_some() {
+        return Err(String::from("option --range needs a single file"));
+    }
+    let to_stdout = options.stdout || options.output.as_deref() == Some("-");
+    if many && to_stdout && command == Command::Compress {
+        return Err(String::from("compressed data of several files can not be written to standard output"));
+    }
+    //Reports must not mix with data written to standard output
+    let report = |line: &str| {
+        if to_stdout || jobs.iter().any(|job| job.file == "-" && (many || options.output.is_none())) {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+    let coding = if many { quiet(options) } else { options.clone() };
+    let status = many && options.verbosity != Verbosity::Quiet && options.stats_format.is_none();
+    let mut csv_header = options.stats_format == Some(StatsFormat::Csv);
+    let (mut failed, mut before, mut after) = (0, 0, 0);
+    for job in jobs.iter() {
+        let result = output_path(command, job, many, options).and_then(|to| {
+            if let (true, Some(parent)) = (options.output.is_some() && to != "-", Path::new(&to).parent()) {
+                fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
             }
-            if res.len() >= self.chars {
-                break;
+            code_file(command, &job.file, &to, &coding).map(|result| (to, result))
+        });
+        match result {
+            Ok((to, (statistics, seconds))) => {
+                let (from_size, to_size) = match command {
+                    Command::Compress => (statistics.chars, statistics.size),
+                    _ => (statistics.size, statistics.chars),
+                };
+                before += from_size;
+                after += to_size;
+                if let (Some(format), None) = (options.stats_format, options.range) {
+                    if csv_header {
+                        report(CSV_HEADER);
+                        csv_header = false;
+                    }
+                    report(&format_statistics(format, command, &job.file, &statistics, seconds));
+                }
+                match (status, options.verbosity) {
+                    (false, _) => {}
+                    (true, Verbosity::Verbose) => report(&format!("{} -> {}: OK ({}B -> {}B)", job.file, to, from_size, to_size)),
+                    (true, _) => report(&format!("{} -> {}: OK", job.file, to)),
+                }
             }
-            loop {
-                if high < 0x80000000 {
-                    //do nothing, bit is a zero
-                } else if low >= 0x80000000 {
-                    value -= 0x80000000;  //subtract one half from all three code values
-                    low -= 0x80000000;
-                    high -= 0x80000000;
-                } else if low >= 0x40000000 && high < 0xC0000000 {
-                    value -= 0x40000000;
-                    low -= 0x40000000;
-                    high -= 0x40000000;
+            Err(e) if many => {
+                //Messages of failures start with the name of the file when they are about it
+                let reason = e.strip_prefix(&format!("{}: ", job.file)).unwrap_or(&e);
+                if status {
+                    report(&format!("{}: FAILED ({})", job.file, reason));
                 } else {
-                    break;
-                }
-                low <<= 1;
-                high <<= 1;
-                high += 1;
-                value <<= 1;
-                if self.get_bit_and_shift() {
-                    value += 1;
+                    eprintln!("coder: {}: {}", job.file, reason);
                 }
+                failed += 1;
             }
-            prob.add(c as usize);
+            Err(e) => return Err(e),
         }
-        self.compute_entropy(&prob);
-        res
     }
+    if status {
+        let (original, compressed) = if command == Command::Compress { (before, after) } else { (after, before) };
+        let ratio = match original {
+            0 => String::new(),
+            _ => format!(" ({:.2}%)", compressed as f64 * 100.0 / original as f64),
+        };
+        report(&format!("{} of {} files coded, {}B -> {}B{}", jobs.len() - failed, jobs.len(), before, after, ratio));
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(format!("{} of {} files failed", failed, jobs.len())),
+    }
+}
 
-    fn encode<T>(data: T) -> Self
-        where T: AsRef<[u8]>
-    {
-        println!("Encoding...");
-        let mut prob = Probabilities::new();
-        let d = data.as_ref();
-        let mut code = Code::new();
-        code.chars = d.len();
-        let mut high = 0xFFFFFFFF_u32;
-        let mut low = 0_u32;
-        let mut pending_bits = 0_u32;
-        let mut position = 0;
-        let mut percent :u32 = 0;
-        for c in d {
-            position += 1;
-            if position*100 / d.len() > percent as usize {
-                Self::print_bar(percent);
-                percent += 1;
-            }
-            let range = high as u64 - low as u64 + 1;
-            high = (low as u64 + (range * prob.pro[*c as usize + 1] as u64) / prob.sum as u64 - 1) as u32;
-            low = (low as u64 + (range * prob.pro[*c as usize] as u64) / prob.sum as u64) as u32;
-            loop {
-                if high < 0x80000000_u32 {
-                    code.add_bit(false);
-                    for _ in 0..pending_bits {
-                        code.add_bit(true);
-                    }
-                    pending_bits = 0;
-                    low <<= 1;
-                    high <<= 1;
-                    high |= 1;
-                } else if low >= 0x80000000_u32 {
-                    code.add_bit(true);
-                    for _ in 0..pending_bits {
-                        code.add_bit(false);
-                    }
-                    pending_bits = 0;
-                    low <<= 1;
-                    high <<= 1;
-                    high |= 1;
-                } else if low >= 0x40000000_u32 && high < 0xC0000000_u32 {
-                    pending_bits += 1;
-                    low <<= 1;
-                    low &= 0x7FFFFFFF;
-                    high <<= 1;
-                    high |= 0x80000001;
-                } else {
-                    break;
-                }
+/**
+    Decompresses `file` without writing the result, verifying checksums
+*/
+fn test_file(file: &str, options: &Options) -> Result<Statistics, Error> {
+    let mut input = Input::open(file)?;
+    decode(&mut input, &mut io::sink(), options)
+}
+
+/**
+    Tests every file, printing OK or FAILED with the reason for each of them
+*/
+fn test_files(files: &[&str], options: &Options) -> Result<(), String> {
+    let quiet = quiet(options);
+    let mut failed = 0;
+    for file in files {
+        match test_file(file, &quiet) {
+            Ok(statistics) => match options.verbosity {
+                Verbosity::Quiet => {}
+                Verbosity::Normal => println!("{}: OK", file),
+                Verbosity::Verbose => println!("{}: OK ({}B -> {}B)", file, statistics.size, statistics.chars),
+            },
+            Err(e) => {
+                println!("{}: FAILED ({})", file, e);
+                failed += 1;
             }
-            prob.add(*c as usize);
         }
-        code.add_bit(true);
-        code.compute_entropy(&prob);
-        code
     }
+    if failed > 0 {
+        return Err(format!("{} of {} files failed", failed, files.len()));
+    }
+    Ok(())
+}
 
-    fn print_compression_statistics(&self){
-        println!("Size before compression: {}B", self.chars);
-        println!("Size after compression: {}B", self.data.len());
-        println!("Compression ratio: {}%", self.data.len() as f32 * 100.0 / self.chars as f32);
-        println!("Entropy: {}", self.entropy);
+/**
+    Quotes `s` as JSON string
+*/
+fn json_string(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
     }
+    res.push('"');
+    res
+}
 
-    fn compute_entropy(&mut self, p :&Probabilities){
-        let sum = p.temp.iter().fold(0, |a, b| a+*b);
-        self.entropy = p.temp.iter().fold(0.0, |acc, x| if *x > 0{
-            acc - (*x as f32/ sum as f32) * ((*x) as f32 / sum as f32).log2()
-        }  else{
-            acc
-        });
+/**
+    Formats `value` as JSON, null for None
+*/
+fn json_option<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| String::from("null"), |value| value.to_string())
+}
+
+/**
+    Quotes `s` as CSV field if it holds a separator, quote or line break
+*/
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        4 => {
-            match args[1].as_str() {
-                "--encode" => {
-                    let mut file;
-                    match File::open(args[2].clone()) {
-                        Ok(f) => file = f,
-                        Err(_error) => {
-                            println!("Unable to open file {}", args[2]);
-                            return;
-                        }
-                    }
-                    let mut data = vec![];
-                    match file.read_to_end(data.as_mut()) {
-                        Err(_e) => {
-                            println!("Unable to read file {}", args[2]);
-                            return;
-                        }
-                        Ok(_) => {},
-                    }
-                    let code = Code::encode(data);
-                    match code.write_to_file(args[3].clone()) {
-                        Err(_e) => {
-                            println!("Unable to write to file {}", args[3]);
-                            return;
-                        }
-                        Ok(()) => {}
-                    }
-                    code.print_compression_statistics()
+/**
+    Name and parameter of model of given kind
+*/
+fn model_name(kind: ModelKind) -> (&'static str, Option<u8>) {
+    match kind {
+        ModelKind::Adaptive => ("adaptive", None),
+        ModelKind::Context(order) => ("context", Some(order)),
+        ModelKind::Ppm(order) => ("ppm", Some(order)),
+        ModelKind::Mixing => ("mixing", None),
+    }
+}
+
+/**
+    Prints metadata of compressed `file` without decoding it
+*/
+fn info_file(file: &str, options: &Options) -> Result<(), String> {
+    let info = match Input::open(file).map_err(Error::from) {
+        Ok(Input::File(f)) => ContainerInfo::read_from(&mut BufReader::new(f)),
+        Ok(Input::Stdin(mut s)) => {
+            let mut data = Vec::new();
+            s.read_to_end(&mut data).map_err(Error::from).and_then(|_| ContainerInfo::read_from(&mut Cursor::new(data)))
+        }
+        Err(e) => Err(e),
+    };
+    let info = info.map_err(|e| format!("{}: {}", file, e))?;
+    let header = info.header;
+    let layout = if header.flags & Header::BLOCKS != 0 {
+        if header.flags & Header::INDEX != 0 { "blocks with index" } else { "blocks" }
+    } else if header.flags & Header::END_SYMBOL != 0 {
+        "single stream ended by end symbol"
+    } else {
+        "single stream"
+    };
+    let checksum = info.checksum.map(|crc| format!("{:08x}", crc));
+    if options.json {
+        let (model, parameter) = model_name(header.model);
+        println!(
+            "{{\"file\":{},\"version\":{},\"model\":\"{}\",\"order\":{},\"layout\":\"{}\",\"original_size\":{},\"compressed_size\":{},\"ratio\":{},\"checksum\":{},\"block_size\":{},\"blocks\":{}}}",
+            json_string(file),
+            header.version,
+            model,
+            json_option(parameter),
+            layout,
+            json_option(info.chars),
+            info.size,
+            json_option(info.ratio()),
+            json_option(checksum.as_deref().map(json_string)),
+            json_option(info.block_size),
+            json_option(info.blocks),
+        );
+        return Ok(());
+    }
+    println!("File: {}", file);
+    println!("Format version: {}", header.version);
+    println!("Model: {}", header.model);
+    println!("Layout: {}", layout);
+    if let (Some(block_size), Some(blocks)) = (info.block_size, info.blocks) {
+        println!("Blocks: {} of up to {}B", blocks, block_size);
+    }
+    match info.chars {
+        Some(chars) => println!("Original size: {}B", chars),
+        None => println!("Original size: unknown"),
+    }
+    println!("Compressed size: {}B", info.size);
+    if let Some(ratio) = info.ratio() {
+        println!("Compression ratio: {:.2}%", ratio * 100.0);
+    }
+    match checksum {
+        Some(crc) => println!("Checksum: CRC-32C {}", crc),
+        None => println!("Checksum: CRC-32C of every block"),
+    }
+    Ok(())
+}
+
+/**
+    Compresses and decompresses `file` in memory and prints sizes and speed
+*/
+fn bench_file(file: &str, options: &Options) -> Result<(), String> {
+    let mut data = Vec::new();
+    Input::open(file)
+        .and_then(|mut input| input.read_to_end(&mut data))
+        .map_err(|e| format!("{}: {}", file, e))?;
+    let options = quiet(options);
+    let start = Instant::now();
+    let mut compressed = Vec::new();
+    let statistics = encode(&mut &data[..], Some(data.len() as u64), &mut compressed, &options)
+        .map_err(|e| format!("{}: {}", file, e))?;
+    let compression = start.elapsed().as_secs_f64();
+    let start = Instant::now();
+    let mut decompressed = Vec::new();
+    decode(&mut &compressed[..], &mut decompressed, &options).map_err(|e| format!("{}: {}", file, e))?;
+    let decompression = start.elapsed().as_secs_f64();
+    if decompressed != data {
+        return Err(format!("{}: decompressed data differs from input", file));
+    }
+    println!("File: {}", file);
+    println!("Model: {}", options.kind);
+    println!("Size before compression: {}B", statistics.chars);
+    println!("Size after compression: {}B", statistics.size);
+    if let Some(ratio) = statistics.ratio() {
+        println!("Compression ratio: {}%", ratio * 100.0);
+    }
+    for (name, seconds) in [("Compression", compression), ("Decompression", decompression)] {
+        match throughput(statistics.chars, seconds) {
+            Some(speed) => println!("{}: {:.3}s, {:.2}MB/s", name, seconds, speed),
+            None => println!("{}: {:.3}s", name, seconds),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Compress,
+    Decompress,
+    Test,
+    Info,
+    Bench,
+    Help,
+    Version,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/**
+    Command line options
+*/
+#[derive(Debug, Clone)]
+struct Options {
+    kind: ModelKind,
+    block_size: Option<usize>,
+    threads: Option<usize>, //None if not given, which keeps single stream
+    index: bool,
+    range: Option<(u64, u64)>,
+    output: Option<String>,
+    stdout: bool,
+    force: bool,
+    keep: bool,
+    recursive: bool,
+    verbosity: Verbosity,
+    json: bool,
+    stats_format: Option<StatsFormat>,
+    files: Vec<String>,
+}
+
+/**
+    Parses value of `option`
+*/
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("option {} needs a value", option))?;
+    value.parse().map_err(|_| format!("invalid value {} of option {}", value, option))
+}
+
+/**
+    Parses command line arguments following program name
+*/
+fn parse_args(args: &[String]) -> Result<(Command, Options), String> {
+    let mut command = None;
+    let mut options = Options {
+        kind: ModelKind::default(),
+        block_size: None,
+        threads: None,
+        index: false,
+        range: None,
+        output: None,
+        stdout: false,
+        force: false,
+        keep: false,
+        recursive: false,
+        verbosity: Verbosity::Normal,
+        json: false,
+        stats_format: None,
+        files: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        //Split clusters of short flags such as -kf
+        let flags: Vec<String> = match arg.strip_prefix('-') {
+            Some(cluster) if !cluster.is_empty() && !cluster.starts_with('-') && cluster.len() > 1 => {
+                cluster.chars().map(|c| format!("-{}", c)).collect()
+            }
+            _ => vec![arg.clone()],
+        };
+        for flag in flags {
+            match flag.as_str() {
+                "-h" | "--help" => command = Some(Command::Help),
+                "-V" | "--version" => command = Some(Command::Version),
+                "-o" | "--output" => options.output = Some(parse_value(&flag, args.next())?),
+                "-c" | "--stdout" => options.stdout = true,
+                "-f" | "--force" => options.force = true,
+                "-k" | "--keep" => options.keep = true,
+                "-r" | "--recursive" => options.recursive = true,
+                "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+                "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+                "--order" => match parse_value(&flag, args.next())? {
+                    order if order <= ContextModel::MAX_ORDER => options.kind = ModelKind::Context(order),
+                    order => return Err(format!("order {} is greater than {}", order, ContextModel::MAX_ORDER)),
+                },
+                "--ppm" => match parse_value(&flag, args.next())? {
+                    order if order <= PpmModel::MAX_ORDER => options.kind = ModelKind::Ppm(order),
+                    order => return Err(format!("order {} is greater than {}", order, PpmModel::MAX_ORDER)),
+                },
+                "--mixing" => options.kind = ModelKind::Mixing,
+                "--block-size" => match parse_value(&flag, args.next())? {
+                    0 => return Err(String::from("block size has to be positive")),
+                    size => options.block_size = Some(size),
+                },
+                "--index" => options.index = true,
+                "--json" => options.json = true,
+                "--stats-format" => {
+                    let value: String = parse_value(&flag, args.next())?;
+                    options.stats_format = Some(match value.as_str() {
+                        "json" => StatsFormat::Json,
+                        "csv" => StatsFormat::Csv,
+                        _ => return Err(format!("invalid statistics format {}, expected json or csv", value)),
+                    });
                 }
-                "--decode" => {
-                    let mut code = Code::read_from_file(args[2].clone()).expect("Unable to open file");
-                    let data = code.decode();
-                    code.print_compression_statistics();
-                    let mut file;
-                    match File::create(args[3].clone()) {
-                        Ok(f) => file = f,
-                        Err(_error) => {
-                            println!("Unable to create file {}", args[2]);
-                            return;
-                        }
-                    }
-                    match file.write_all(data.as_ref()) {
-                        Err(_e) => {
-                            println!("Unable to write file {}", args[2]);
-                            return;
-                        }
-                        Ok(_) => {},
-                    }
-                    match file.sync_all() {
-                        Err(_e) => {
-                            println!("Unable to write file {}", args[2]);
-                            return;
-                        }
-                        Ok(_) => {},
-                    }
+                "--threads" => match parse_value(&flag, args.next())? {
+                    0 => return Err(String::from("number of threads has to be positive")),
+                    threads => options.threads = Some(threads),
+                },
+                "--range" => {
+                    let value: String = parse_value(&flag, args.next())?;
+                    let range = value.split_once(':').and_then(|(offset, len)| Some((offset.parse().ok()?, len.parse().ok()?)));
+                    options.range = Some(range.ok_or_else(|| format!("invalid range {}, expected <OFFSET>:<LEN>", value))?);
                 }
-                _ => println!("Wrong arguments please try {} <--encode | --decode> <file_from> <file_to>", args[0])
+                flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+                name if command.is_none() => {
+                    command = Some(match name {
+                        "compress" => Command::Compress,
+                        "decompress" => Command::Decompress,
+                        "test" => Command::Test,
+                        "info" => Command::Info,
+                        "bench" => Command::Bench,
+                        _ => return Err(format!("unknown command {}", name)),
+                    })
+                }
+                file => options.files.push(file.to_string()),
             }
         }
-        _ => println!("Wrong arguments please try {} <--encode | --decode> <file_from> <file_to>", args[0])
     }
-}
\ No newline at end of file
+    let command = command.ok_or_else(|| String::from("no command given"))?;
+    Ok((command, options))
+}
+
+/**
+    Runs `command`, returning message describing failure
+*/
+fn run(command: Command, options: &Options) -> Result<(), String> {
+    match command {
+        Command::Help => {
+            println!("{}", HELP);
+            return Ok(());
+        }
+        Command::Version => {
+            println!("coder {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Command::Test => {
+            let files: Vec<&str> = options.files.iter().map(|f| f.as_str()).collect();
+            return test_files(if files.is_empty() { &["-"] } else { &files }, options);
+        }
+        _ => {}
+    }
+    if let Command::Compress | Command::Decompress = command {
+        return code_files(command, options);
+    }
+    let file = match options.files[..] {
+        [] => "-",
+        [ref file] => file.as_str(),
+        _ => return Err(String::from("only one file can be given")),
+    };
+    match command {
+        Command::Info => info_file(file, options),
+        Command::Bench => bench_file(file, options),
+        _ => unreachable!(),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let (command, options) = match parse_args(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("coder: {}\nTry 'coder --help' for more information.", message);
+            return ExitCode::from(2);
+        }
+    };
+    match run(command, &options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("coder: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+        Runs coder with `args`, which are split at spaces
+    */
+    fn coder(args: &str) -> Result<(), String> {
+        let args: Vec<String> = args.split(' ').map(String::from).collect();
+        let (command, options) = parse_args(&args)?;
+        run(command, &options)
+    }
+
+    #[test]
+    fn range_keeps_input() {
+        let dir = env::temp_dir().join(format!("coder-range-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("t").to_string_lossy().into_owned();
+        let data: Vec<u8> = (0..1000).map(|i| (i % 26) as u8 + b'a').collect();
+        fs::write(&file, &data).unwrap();
+        coder(&format!("compress -q --index {}", file)).unwrap();
+        coder(&format!("decompress -q --range 5:20 {}.ac -o {}.part", file, file)).unwrap();
+        assert!(Path::new(&format!("{}.ac", file)).exists());
+        assert_eq!(fs::read(format!("{}.part", file)).unwrap(), &data[5..25]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
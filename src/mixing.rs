@@ -0,0 +1,368 @@
+use crate::model::Model;
+
+/**
+    Logistic function scaled to 12 bits: `4096 / (1 + e^(-d / 256))`
+*/
+fn squash(d: i32) -> i32 {
+    const T: [i32; 33] = [
+        1, 2, 3, 6, 10, 16, 27, 45, 73, 120, 194, 310, 488, 747, 1101, 1546, 2047,
+        2549, 2994, 3348, 3607, 3785, 3901, 3975, 4022, 4050, 4068, 4079, 4085, 4089, 4092, 4093, 4094,
+    ];
+    if d > 2047 {
+        return 4095;
+    }
+    if d < -2047 {
+        return 1;
+    }
+    let w = d & 127;
+    let i = ((d >> 7) + 16) as usize;
+    (T[i] * (128 - w) + T[i + 1] * w + 64) >> 7
+}
+
+/**
+    Inverse of `squash`: `ln(p / (1 - p))` scaled by 256
+*/
+#[derive(Debug, Clone)]
+struct Stretch {
+    table: Vec<i16>,
+}
+
+impl Stretch {
+    fn new() -> Self {
+        let mut table = vec![2047_i16; 4096];
+        let mut pi = 0;
+        for x in -2047..=2047 {
+            let v = squash(x) as usize;
+            for t in table.iter_mut().take(v + 1).skip(pi) {
+                *t = x as i16;
+            }
+            pi = pi.max(v + 1);
+        }
+        Self { table }
+    }
+
+    fn get(&self, p: i32) -> i32 {
+        self.table[p as usize] as i32
+    }
+}
+
+/**
+    Table of adaptive bit probabilities
+
+    Every entry keeps a 22-bit probability of a one and the number of times
+    it was updated, so new entries adapt quickly and old ones settle.
+*/
+#[derive(Debug, Clone)]
+struct StateMap {
+    table: Vec<u32>,
+    limit: u32,
+}
+
+impl StateMap {
+    fn new(size: usize, limit: u32) -> Self {
+        Self {
+            table: vec![1 << 31; size],
+            limit,
+        }
+    }
+
+    /**
+        Probability of a one at `index` in 12 bits
+    */
+    fn p(&self, index: usize) -> i32 {
+        (self.table[index] >> 20) as i32
+    }
+
+    fn update(&mut self, index: usize, bit: u32) {
+        let t = self.table[index];
+        let n = t & 1023;
+        let p = (t >> 10) as i64;
+        let t = if n < self.limit { t + 1 } else { (t & 0xFFFFFC00) | self.limit };
+        let rate = 16384 / (n as i64 * 2 + 3);
+        let delta = ((((bit as i64) << 22) - p) >> 3) * rate;
+        self.table[index] = (t as i64 + (delta & !1023)) as u32;
+    }
+}
+
+/**
+    Online trained linear combination of predictions in the logistic domain
+*/
+#[derive(Debug, Clone)]
+struct Mixer {
+    n: usize, //number of inputs
+    inputs: Vec<i32>,
+    weights: Vec<i32>,
+    selected: usize, //offset of weight set in use
+    p: i32,
+}
+
+impl Mixer {
+    const LEARNING_RATE: i32 = 6;
+    const MAX_WEIGHT: i32 = 1 << 22;
+
+    fn new(inputs: usize, sets: usize) -> Self {
+        Self {
+            n: inputs,
+            inputs: Vec::with_capacity(inputs),
+            weights: vec![(1 << 16) / inputs as i32; inputs * sets],
+            selected: 0,
+            p: 2048,
+        }
+    }
+
+    fn add(&mut self, st: i32) {
+        self.inputs.push(st);
+    }
+
+    /**
+        Combines inputs with weight set `set` and returns probability of a one
+    */
+    fn mix(&mut self, set: usize) -> i32 {
+        self.selected = set * self.n;
+        let weights = &self.weights[self.selected..];
+        let dot = self.inputs.iter().zip(weights).fold(0_i64, |a, (x, w)| a + *x as i64 * *w as i64);
+        self.p = squash((dot >> 16).clamp(-2047, 2047) as i32);
+        self.p
+    }
+
+    fn update(&mut self, bit: u32) {
+        let err = (((bit as i32) << 12) - self.p) * Self::LEARNING_RATE;
+        let weights = &mut self.weights[self.selected..];
+        for (x, w) in self.inputs.iter().zip(weights.iter_mut()) {
+            *w = (*w + ((*x * err) >> 15)).clamp(-Self::MAX_WEIGHT, Self::MAX_WEIGHT);
+        }
+        self.inputs.clear();
+    }
+}
+
+/**
+    Predicts next bit from the character following the last occurrence of
+    the current context
+*/
+#[derive(Debug, Clone)]
+struct MatchModel {
+    history: Vec<u8>,
+    table: Vec<u32>, //position after last occurrence of hash of last MIN_LENGTH characters
+    pointer: usize, //position of predicted character in history, valid when length > 0
+    length: usize,
+    map: StateMap,
+}
+
+impl MatchModel {
+    const MIN_LENGTH: usize = 6;
+    const MAX_LENGTH: usize = 65535;
+    const HISTORY_SIZE: usize = 1 << 24;
+    const TABLE_BITS: u32 = 20;
+
+    fn new() -> Self {
+        Self {
+            history: Vec::new(),
+            table: vec![0; 1 << Self::TABLE_BITS],
+            pointer: 0,
+            length: 0,
+            map: StateMap::new(2 * 64, 1023),
+        }
+    }
+
+    /**
+        Adds coded character and looks for a new match if the current one ended
+    */
+    fn add(&mut self, c: u8) {
+        if self.history.len() == Self::HISTORY_SIZE {
+            //Start over instead of growing without bound
+            self.history.clear();
+            self.table.iter_mut().for_each(|t| *t = 0);
+            self.length = 0;
+        }
+        if self.length > 0 && self.history[self.pointer] == c {
+            self.length = (self.length + 1).min(Self::MAX_LENGTH);
+            self.pointer += 1;
+        } else {
+            self.length = 0;
+        }
+        self.history.push(c);
+        let n = self.history.len();
+        if n < Self::MIN_LENGTH {
+            return;
+        }
+        let h = self.history[n - Self::MIN_LENGTH..].iter()
+            .fold(0_u32, |h, c| (h ^ *c as u32).wrapping_mul(0x2F0B3A49));
+        let slot = (h >> (32 - Self::TABLE_BITS)) as usize;
+        if self.length == 0 {
+            let candidate = self.table[slot] as usize;
+            if candidate > 0 {
+                let mut length = 0;
+                while length < n.min(candidate) && length < 64
+                    && self.history[candidate - 1 - length] == self.history[n - 1 - length] {
+                    length += 1;
+                }
+                if length >= Self::MIN_LENGTH {
+                    self.length = length;
+                    self.pointer = candidate;
+                }
+            }
+        }
+        self.table[slot] = n as u32;
+    }
+
+    /**
+        Index to `map` for partially coded character `c0`, None when there is no prediction
+    */
+    fn context(&self, c0: u32, bits: u32) -> Option<usize> {
+        if self.length == 0 || self.pointer >= self.history.len() {
+            return None;
+        }
+        let expected = self.history[self.pointer] as u32 | 256;
+        if expected >> (8 - bits) != c0 {
+            return None;
+        }
+        let bit = (expected >> (7 - bits)) & 1;
+        let length = if self.length < 16 { self.length } else { 16 + (self.length.min(1000) / 64) };
+        Some(length.min(31) * 2 + bit as usize)
+    }
+}
+
+/**
+    Context mixing model
+
+    Characters are coded bit by bit. Every bit is predicted by order-0 to
+    order-6 context models, a word model and a match model; their predictions
+    are combined by a mixer trained online in the logistic domain. Slower
+    than other models and uses about 150 MB of memory, but gives the best
+    compression.
+*/
+#[derive(Debug, Clone)]
+pub struct MixingModel {
+    stretch: Stretch,
+    maps: Vec<StateMap>,
+    contexts: Vec<u32>, //hashes of contexts of every map for current character
+    indexes: Vec<usize>, //entries of maps used for current bit
+    mixer: Mixer,
+    matches: MatchModel,
+    match_index: Option<usize>,
+    history: u64, //last characters, most recent in lowest byte
+    word: u32, //hash of letters of current word
+    c0: u32, //bits of current character coded so far with leading one
+    bits: u32, //number of bits of current character coded so far
+    p: i32, //probability of a one in 12 bits
+}
+
+impl MixingModel {
+    const ORDERS: usize = 7;
+    const TABLE_BITS: u32 = 22;
+    const INPUTS: usize = Self::ORDERS + 3;
+
+    /**
+        Creates model with empty history
+    */
+    pub fn new() -> Self {
+        let maps = Self::ORDERS + 1;
+        let mut model = Self {
+            stretch: Stretch::new(),
+            maps: (0..maps).map(|_| StateMap::new(1 << Self::TABLE_BITS, 127)).collect(),
+            contexts: vec![0; maps],
+            indexes: vec![0; maps],
+            mixer: Mixer::new(Self::INPUTS, 256),
+            matches: MatchModel::new(),
+            match_index: None,
+            history: 0,
+            word: 0,
+            c0: 1,
+            bits: 0,
+            p: 2048,
+        };
+        model.set_contexts();
+        model.predict();
+        model
+    }
+
+    fn hash(a: u32, b: u32) -> u32 {
+        let h = a.wrapping_mul(0x9E3779B1) ^ b.wrapping_mul(0x85EBCA6B).rotate_left(13);
+        h ^ (h >> 15)
+    }
+
+    /**
+        Computes hashes of contexts at the start of a character
+    */
+    fn set_contexts(&mut self) {
+        for order in 0..Self::ORDERS {
+            let mask = if order == 0 { 0 } else { u64::MAX >> (64 - 8 * order) };
+            let h = self.history & mask;
+            self.contexts[order] = Self::hash(Self::hash(h as u32, (h >> 32) as u32), order as u32);
+        }
+        self.contexts[Self::ORDERS] = Self::hash(self.word, (self.history & 0xFF) as u32 | 0x10000);
+    }
+
+    /**
+        Computes probability of next bit
+    */
+    fn predict(&mut self) {
+        for (i, map) in self.maps.iter().enumerate() {
+            let h = Self::hash(self.contexts[i], self.c0);
+            self.indexes[i] = (h >> (32 - Self::TABLE_BITS)) as usize;
+            self.mixer.add(self.stretch.get(map.p(self.indexes[i])));
+        }
+        self.match_index = self.matches.context(self.c0, self.bits);
+        match self.match_index {
+            Some(i) => self.mixer.add(self.stretch.get(self.matches.map.p(i))),
+            None => self.mixer.add(0),
+        }
+        self.mixer.add(256);
+        self.p = self.mixer.mix(self.c0 as usize).clamp(1, 4095);
+    }
+}
+
+impl Default for MixingModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Model for MixingModel {
+    fn total(&self) -> u64 {
+        4096
+    }
+
+    fn range(&self, symbol: usize) -> (u64, u64) {
+        let zero = (4096 - self.p) as u64;
+        match symbol {
+            0 => (0, zero),
+            _ => (zero, 4096),
+        }
+    }
+
+    fn symbol(&self, frequency: u64) -> usize {
+        (frequency >= (4096 - self.p) as u64) as usize
+    }
+
+    fn update(&mut self, symbol: usize) {
+        let bit = symbol as u32;
+        for (map, index) in self.maps.iter_mut().zip(self.indexes.iter()) {
+            map.update(*index, bit);
+        }
+        if let Some(i) = self.match_index {
+            self.matches.map.update(i, bit);
+        }
+        self.mixer.update(bit);
+        self.c0 = (self.c0 << 1) | bit;
+        self.bits += 1;
+        if self.bits == 8 {
+            let c = (self.c0 & 0xFF) as u8;
+            self.history = (self.history << 8) | c as u64;
+            self.word = if c.is_ascii_alphabetic() {
+                Self::hash(self.word, c.to_ascii_lowercase() as u32)
+            } else {
+                0
+            };
+            self.matches.add(c);
+            self.c0 = 1;
+            self.bits = 0;
+            self.set_contexts();
+        }
+        self.predict();
+    }
+
+    fn binary(&self) -> bool {
+        true
+    }
+}
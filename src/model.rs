@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::context::ContextModel;
+use crate::mixing::MixingModel;
+use crate::ppm::PpmModel;
+use crate::probabilities::Probabilities;
+
+/// Largest total of frequencies the coder can handle without losing precision
+pub const MAX_TOTAL: u64 = 1 << 30;
+
+/// Symbol coded when a model can not code a character in its current state
+pub const ESCAPE: usize = usize::MAX;
+
+/**
+    Adaptive probability model driving the arithmetic coder
+
+    Probabilities are described by integer frequencies: symbol `s` owns the
+    range `range(s)` of cumulative frequencies out of `total()`. Encoder and
+    decoder call `update` with every coded symbol in the same order, so a model
+    may change its frequencies after each symbol as long as it does so
+    deterministically.
+
+    ```
+    use std::io::{Read, Write};
+    use arithmetic_coder::{ArithmeticDecoder, ArithmeticEncoder, Model};
+
+    /// Every character is equally probable
+    struct Uniform;
+
+    impl Model for Uniform {
+        fn total(&self) -> u64 { 256 }
+        fn range(&self, symbol: usize) -> (u64, u64) { (symbol as u64, symbol as u64 + 1) }
+        fn symbol(&self, frequency: u64) -> usize { frequency as usize }
+        fn update(&mut self, _symbol: usize) {}
+    }
+
+    let mut encoder = ArithmeticEncoder::with_model(Vec::new(), 5, Uniform).unwrap();
+    encoder.write_all(b"hello").unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut decoder = ArithmeticDecoder::with_model(&compressed[..], Uniform).unwrap();
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, b"hello");
+    ```
+*/
+pub trait Model {
+    /**
+        Sum of frequencies of all symbols, not greater than `MAX_TOTAL`
+    */
+    fn total(&self) -> u64;
+
+    /**
+        Cumulative frequencies of symbols before `symbol` and up to `symbol` inclusive
+    */
+    fn range(&self, symbol: usize) -> (u64, u64);
+
+    /**
+        Symbol whose cumulative frequency range contains `frequency`
+    */
+    fn symbol(&self, frequency: u64) -> usize;
+
+    /**
+        Update model after `symbol` was coded
+    */
+    fn update(&mut self, symbol: usize);
+
+    /**
+        Whether `symbol` can be coded in the current state
+
+        While it can not, the coder codes `ESCAPE` and calls `update(ESCAPE)`,
+        letting the model move to a state that offers the symbol. Models giving
+        every symbol a frequency keep the default.
+    */
+    fn contains(&self, _symbol: usize) -> bool {
+        true
+    }
+
+    /**
+        Whether the model codes characters bit by bit
+
+        Binary models have symbols 0 and 1 and are given the bits of every
+        character starting from the most significant one.
+    */
+    fn binary(&self) -> bool {
+        false
+    }
+}
+
+impl<M: Model + ?Sized> Model for Box<M> {
+    fn total(&self) -> u64 {
+        (**self).total()
+    }
+
+    fn range(&self, symbol: usize) -> (u64, u64) {
+        (**self).range(symbol)
+    }
+
+    fn symbol(&self, frequency: u64) -> usize {
+        (**self).symbol(frequency)
+    }
+
+    fn update(&mut self, symbol: usize) {
+        (**self).update(symbol)
+    }
+
+    fn contains(&self, symbol: usize) -> bool {
+        (**self).contains(symbol)
+    }
+
+    fn binary(&self) -> bool {
+        (**self).binary()
+    }
+}
+
+/**
+    Built-in models selectable when compressing
+
+    Kind of model is stored in the `Header` of compressed data, so the
+    decoder can recreate the model the data was encoded with.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModelKind {
+    /// Order-0 `Probabilities` recomputed every cycle
+    #[default]
+    Adaptive,
+    /// `ContextModel` of given order
+    Context(u8),
+    /// `PpmModel` of given order
+    Ppm(u8),
+    /// `MixingModel`
+    Mixing,
+}
+
+impl ModelKind {
+    /**
+        Creates new model of this kind
+
+        Panics if order is greater than `ContextModel::MAX_ORDER` or
+        `PpmModel::MAX_ORDER`.
+    */
+    pub fn build(&self) -> Box<dyn Model> {
+        match *self {
+            ModelKind::Adaptive => Box::new(Probabilities::new()),
+            ModelKind::Context(order) => Box::new(ContextModel::new(order)),
+            ModelKind::Ppm(order) => Box::new(PpmModel::new(order)),
+            ModelKind::Mixing => Box::new(MixingModel::new()),
+        }
+    }
+
+    /**
+        Identifier and parameter of this kind stored in the header
+    */
+    pub(crate) fn to_bytes(self) -> [u8; 2] {
+        match self {
+            ModelKind::Adaptive => [0, 0],
+            ModelKind::Context(order) => [1, order],
+            ModelKind::Ppm(order) => [2, order],
+            ModelKind::Mixing => [3, 0],
+        }
+    }
+
+    /**
+        Reads kind stored by `to_bytes`, None if it is not valid
+    */
+    pub(crate) fn from_bytes(bytes: [u8; 2]) -> Option<Self> {
+        match bytes {
+            [0, 0] => Some(ModelKind::Adaptive),
+            [1, order] if order <= ContextModel::MAX_ORDER => Some(ModelKind::Context(order)),
+            [2, order] if order <= PpmModel::MAX_ORDER => Some(ModelKind::Ppm(order)),
+            [3, 0] => Some(ModelKind::Mixing),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelKind::Adaptive => write!(f, "adaptive order-0"),
+            ModelKind::Context(order) => write!(f, "order-{} context", order),
+            ModelKind::Ppm(order) => write!(f, "order-{} PPM", order),
+            ModelKind::Mixing => write!(f, "context mixing"),
+        }
+    }
+}
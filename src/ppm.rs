@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use crate::model::{Model, ESCAPE};
+
+/**
+    Characters seen in one context with their counts
+*/
+#[derive(Debug, Clone, Default)]
+struct Context {
+    symbols: Vec<(u8, u16)>,
+    sum: u32,
+}
+
+impl Context {
+    const LIMIT: u32 = 1 << 13; //Select sum of counts after which counts are halved
+
+    fn add(&mut self, symbol: u8) {
+        match self.symbols.iter_mut().find(|(s, _)| *s == symbol) {
+            Some((_, count)) => *count += 1,
+            None => self.symbols.push((symbol, 1)),
+        }
+        self.sum += 1;
+        if self.sum > Self::LIMIT {
+            self.sum = 0;
+            for (_, count) in self.symbols.iter_mut() {
+                *count = count.div_ceil(2);
+                self.sum += *count as u32;
+            }
+        }
+    }
+}
+
+/**
+    Prediction by partial matching model
+
+    Characters are predicted by the longest context of at most `order`
+    preceding characters in which they were seen before. When a character did
+    not occur in the current context, `ESCAPE` is coded and the next shorter
+    context is tried, down to a uniform distribution over all characters.
+    Escape frequencies are estimated with method D and characters already
+    offered by longer contexts are excluded from shorter ones.
+*/
+#[derive(Debug, Clone)]
+pub struct PpmModel {
+    order: u8,
+    history: u64, //last `order` characters, most recent in lowest byte
+    seen: u8, //number of characters in history, up to order
+    contexts: Vec<HashMap<u64, Context>>, //contexts of every order
+    excluded: Vec<bool>,
+    current: Option<u8>, //order of context used now, None for uniform distribution
+}
+
+impl PpmModel {
+    /// Largest supported order
+    pub const MAX_ORDER: u8 = 8;
+    /// Number of contexts kept before all of them are discarded
+    pub const MAX_CONTEXTS: usize = 1 << 20;
+
+    /**
+        Creates model of given order
+
+        Panics if `order` is greater than `MAX_ORDER`.
+    */
+    pub fn new(order: u8) -> Self {
+        assert!(order <= Self::MAX_ORDER, "order {} is greater than {}", order, Self::MAX_ORDER);
+        let mut model = Self {
+            order,
+            history: 0,
+            seen: 0,
+            contexts: vec![HashMap::new(); order as usize + 1],
+            excluded: vec![false; 256],
+            current: None,
+        };
+        model.select_context(Some(0));
+        model
+    }
+
+    /**
+        Order of the model
+    */
+    pub fn order(&self) -> u8 {
+        self.order
+    }
+
+    fn key(&self, order: u8) -> u64 {
+        if order == 0 {
+            0
+        } else {
+            self.history & (u64::MAX >> (64 - 8 * order as u32))
+        }
+    }
+
+    fn context(&self) -> Option<&Context> {
+        self.current.and_then(|order| self.contexts[order as usize].get(&self.key(order)))
+    }
+
+    /**
+        Makes current the longest context not longer than `start` that offers
+        a character not excluded yet
+    */
+    fn select_context(&mut self, start: Option<u8>) {
+        let mut order = start;
+        while let Some(o) = order {
+            let offers = self.contexts[o as usize].get(&self.key(o))
+                .is_some_and(|c| c.symbols.iter().any(|(s, _)| !self.excluded[*s as usize]));
+            if offers {
+                break;
+            }
+            order = o.checked_sub(1);
+        }
+        self.current = order;
+    }
+
+    /**
+        Frequencies of characters of current context which are not excluded
+    */
+    fn frequencies(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        let context = self.context();
+        let uniform = if context.is_none() { 0..256 } else { 0..0 };
+        context.into_iter()
+            .flat_map(|c| c.symbols.iter().map(|(s, count)| (*s as usize, 2 * *count as u64 - 1)))
+            .chain(uniform.map(|s| (s, 1)))
+            .filter(move |(s, _)| !self.excluded[*s])
+    }
+
+    /**
+        Escape frequency: number of distinct characters offered by current context
+    */
+    fn escape(&self) -> u64 {
+        match self.current {
+            Some(_) => self.frequencies().count() as u64,
+            None => 0,
+        }
+    }
+}
+
+impl Model for PpmModel {
+    fn total(&self) -> u64 {
+        self.frequencies().map(|(_, f)| f).sum::<u64>() + self.escape()
+    }
+
+    fn range(&self, symbol: usize) -> (u64, u64) {
+        let mut start = 0;
+        for (s, f) in self.frequencies() {
+            if s == symbol {
+                return (start, start + f);
+            }
+            start += f;
+        }
+        (start, start + self.escape())
+    }
+
+    fn symbol(&self, frequency: u64) -> usize {
+        let mut end = 0;
+        for (s, f) in self.frequencies() {
+            end += f;
+            if frequency < end {
+                return s;
+            }
+        }
+        ESCAPE
+    }
+
+    fn contains(&self, symbol: usize) -> bool {
+        self.frequencies().any(|(s, _)| s == symbol)
+    }
+
+    fn update(&mut self, symbol: usize) {
+        if symbol == ESCAPE {
+            let offered: Vec<usize> = self.frequencies().map(|(s, _)| s).collect();
+            for s in offered {
+                self.excluded[s] = true;
+            }
+            let next = self.current.and_then(|order| order.checked_sub(1));
+            self.select_context(next);
+            return;
+        }
+        let count = self.contexts.iter().map(|c| c.len()).sum::<usize>();
+        if count > Self::MAX_CONTEXTS {
+            for contexts in self.contexts.iter_mut() {
+                contexts.clear();
+            }
+        }
+        for order in 0..=self.seen {
+            let key = self.key(order);
+            self.contexts[order as usize].entry(key).or_default().add(symbol as u8);
+        }
+        if self.order > 0 {
+            self.history = (self.history << 8) | symbol as u64;
+            self.seen = (self.seen + 1).min(self.order);
+        }
+        for e in self.excluded.iter_mut() {
+            *e = false;
+        }
+        self.select_context(Some(self.seen));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::model::Model;
+
+/**
+    Struct representing probabilities of characters in file
+
+    Default order-0 model: frequencies of characters are counted and
+    probabilities are recomputed from them every `CYCLE` characters.
+*/
+#[derive(Debug)]
+pub struct Probabilities {
+    sum: u64,
+    pro: Vec<u64>,
+    temp: Vec<u64>,
+    cycle: u64,
+}
+
+impl Probabilities {
+    const PRECISION: u64 = 1048576 * 1024; //Select the denominator of probabilities
+    const CYCLE: u64 = 64; //Select length of cycle
+
+    pub fn new() -> Self {
+        let mut pro = vec![0; 257];
+        for i in 0..257_u64 {
+            pro[i as usize] = i;
+        }
+        Probabilities {
+            sum: 256,
+            temp: vec![0; 256],
+            pro,
+            cycle: 0,
+        }
+    }
+
+    /**
+    Add char to probability computations
+    */
+    fn add(&mut self, to_add: usize) {
+        self.temp[to_add] += 1;
+        self.cycle += 1;
+        if self.cycle >= Self::CYCLE {
+            self.cycle = 0;
+            self.update_probabilities();
+        }
+    }
+
+    /**
+    Update probabilities
+    */
+    fn update_probabilities(&mut self) {
+        let sum = self.temp.iter().fold(0_u64, |a, b| a + *b);
+        let c = (Self::PRECISION - 256) as f64 / sum as f64;
+        let mut t: Vec<u64> = vec![];
+        //Compute probabilities to add up to PRECISION
+        for v in self.temp.iter() {
+            t.push((*v as f64 * c).floor() as u64 + 1);
+        }
+        let all = t.iter().fold(0_u64, |a, b| a + *b);
+        let deficit = Self::PRECISION - all;
+        //Rest add to first elements
+        for v in t.iter_mut().take(deficit as usize) {
+            *v += 1;
+        }
+        let mut temp = 0;
+        //Update pro array
+        self.pro = vec![0; 257];
+        for i in 1_usize..257 {
+            temp += t[i - 1];
+            self.pro[i] = temp;
+        }
+        assert_eq!(self.pro[256], Self::PRECISION);
+        self.sum = Self::PRECISION;
+    }
+}
+
+impl Default for Probabilities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Model for Probabilities {
+    fn total(&self) -> u64 {
+        self.sum
+    }
+
+    fn range(&self, symbol: usize) -> (u64, u64) {
+        (self.pro[symbol], self.pro[symbol + 1])
+    }
+
+    fn symbol(&self, frequency: u64) -> usize {
+        //Last character whose cumulative frequency does not exceed `frequency`
+        self.pro[1..256].partition_point(|p| *p <= frequency)
+    }
+
+    fn update(&mut self, symbol: usize) {
+        self.add(symbol);
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::{self, Read, Write};
+
+/**
+    Receiver of progress of long running coding
+
+    Any closure taking number of characters done and their total, if it is
+    known, can be used as progress.
+*/
+pub trait Progress {
+    /**
+        Called after every chunk with number of characters done so far and their total if it is known
+    */
+    fn update(&mut self, done: u64, total: Option<u64>);
+
+    /**
+        Called once after the last character
+    */
+    fn finish(&mut self) {}
+}
+
+impl<F: FnMut(u64, Option<u64>)> Progress for F {
+    fn update(&mut self, done: u64, total: Option<u64>) {
+        self(done, total)
+    }
+}
+
+/**
+    Copies `total` characters, or everything if their number is not known,
+    from `input` to `output`, reporting progress after every chunk
+
+    Fails with `io::ErrorKind::UnexpectedEof` if `input` ends before `total`
+    characters. Returns number of copied characters.
+
+    ```
+    use arithmetic_coder::{copy_with_progress, ArithmeticEncoder};
+
+    let data = b"abracadabra";
+    let mut encoder = ArithmeticEncoder::new(Vec::new(), data.len() as u64).unwrap();
+    let mut reported = 0;
+    copy_with_progress(&mut &data[..], &mut encoder, Some(data.len() as u64), &mut |done, _| reported = done).unwrap();
+    assert_eq!(reported, 11);
+    ```
+*/
+pub fn copy_with_progress<R, W, P>(input: &mut R, output: &mut W, total: Option<u64>, progress: &mut P) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+    P: Progress + ?Sized,
+{
+    const CHUNK: usize = 65536;
+    let mut buffer = vec![0; CHUNK];
+    let mut done = 0;
+    loop {
+        let limit = match total {
+            Some(total) => (total - done).min(CHUNK as u64) as usize,
+            None => CHUNK,
+        };
+        if limit == 0 {
+            break;
+        }
+        let n = match input.read(&mut buffer[..limit]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if n == 0 {
+            if total.is_some() {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            break;
+        }
+        output.write_all(&buffer[..n])?;
+        done += n as u64;
+        progress.update(done, total);
+    }
+    progress.finish();
+    Ok(done)
+}
@@ -0,0 +1,310 @@
+use std::io::{self, Read, Write};
+
+use crate::error::Error;
+use crate::model::MAX_TOTAL;
+
+/// Start of range of end-of-stream symbol, which gets the smallest possible frequency
+const END: u64 = MAX_TOTAL - 1;
+
+/**
+    Arithmetic coder state shared by all encoders
+
+    Codes ranges of cumulative frequencies into bits with scaling. Finished
+    bytes are collected in a buffer which owners push to their writer.
+*/
+#[derive(Debug)]
+pub(crate) struct RangeEncoder {
+    high: u32,
+    low: u32,
+    pending_bits: u32,
+    buffer: Vec<u8>,
+    byte: u8,
+    bits: u8,
+}
+
+impl RangeEncoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            high: 0xFFFFFFFF,
+            low: 0,
+            pending_bits: 0,
+            buffer: Vec::new(),
+            byte: 0,
+            bits: 0,
+        }
+    }
+
+    /**
+        Number of finished bytes waiting in buffer
+    */
+    pub(crate) fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /**
+        Writes finished bytes to `w` and empties buffer
+    */
+    pub(crate) fn flush_to<W: Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /**
+        Writes terminating bits and pads last byte with zeros
+
+        Two bits select a quarter lying inside the final interval, so the
+        code decodes correctly whatever bytes follow it.
+    */
+    pub(crate) fn finish(&mut self) {
+        self.pending_bits += 1;
+        self.add_bit_with_pending(self.low >= 0x40000000);
+        if self.bits > 0 {
+            self.buffer.push(self.byte << (8 - self.bits));
+            self.byte = 0;
+            self.bits = 0;
+        }
+    }
+
+    /**
+        Add bit to code
+    */
+    fn add_bit(&mut self, c: bool) {
+        self.byte = (self.byte << 1) | c as u8;
+        self.bits += 1;
+        if self.bits == 8 {
+            self.buffer.push(self.byte);
+            self.byte = 0;
+            self.bits = 0;
+        }
+    }
+
+    /**
+        Add bit followed by all pending bits of opposite value
+    */
+    fn add_bit_with_pending(&mut self, c: bool) {
+        self.add_bit(c);
+        for _ in 0..self.pending_bits {
+            self.add_bit(!c);
+        }
+        self.pending_bits = 0;
+    }
+
+    /**
+        Narrows interval to cumulative frequencies `start..end` out of `total`
+    */
+    pub(crate) fn encode(&mut self, start: u64, end: u64, total: u64) {
+        let range = self.high as u64 - self.low as u64 + 1;
+        self.high = (self.low as u64 + (range * end) / total - 1) as u32;
+        self.low = (self.low as u64 + (range * start) / total) as u32;
+        loop {
+            if self.high < 0x80000000_u32 {
+                self.add_bit_with_pending(false);
+                self.low <<= 1;
+                self.high <<= 1;
+                self.high |= 1;
+            } else if self.low >= 0x80000000_u32 {
+                self.add_bit_with_pending(true);
+                self.low <<= 1;
+                self.high <<= 1;
+                self.high |= 1;
+            } else if self.low >= 0x40000000_u32 && self.high < 0xC0000000_u32 {
+                self.pending_bits += 1;
+                self.low <<= 1;
+                self.low &= 0x7FFFFFFF;
+                self.high <<= 1;
+                self.high |= 0x80000001;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /**
+        Codes whether stream ends here or another character follows
+    */
+    pub(crate) fn encode_end(&mut self, end: bool) {
+        if end {
+            self.encode(END, MAX_TOTAL, MAX_TOTAL);
+        } else {
+            self.encode(0, END, MAX_TOTAL);
+        }
+    }
+}
+
+/**
+    Arithmetic decoder state shared by all decoders
+
+    Pulls coded bytes from the inner reader in small chunks when more bits
+    are needed.
+*/
+#[derive(Debug)]
+pub(crate) struct RangeDecoder<R: Read> {
+    inner: R,
+    high: u32,
+    low: u32,
+    value: u32,
+    buffer: Vec<u8>,
+    position: usize, //index of next bit in buffer
+    exhausted: bool,
+    missing: u32, //number of bits read past end of inner reader
+    consumed: u64, //number of bits read since start of code
+    fetched: u64, //number of bytes pulled from inner reader
+}
+
+impl<R: Read> RangeDecoder<R> {
+    const BIN: [u8; 8] = [128, 64, 32, 16, 8, 4, 2, 1];
+    const BUFFER_SIZE: usize = 4096;
+    const KEEP: usize = 4; //Select number of bytes kept in buffer on refill
+
+    /**
+        Creates decoder reading first bits of code from `inner`
+    */
+    pub(crate) fn new(inner: R) -> Result<Self, Error> {
+        let mut decoder = Self {
+            inner,
+            high: 0xFFFFFFFF,
+            low: 0,
+            value: 0,
+            buffer: Vec::new(),
+            position: 0,
+            exhausted: false,
+            missing: 0,
+            consumed: 0,
+            fetched: 0,
+        };
+        for _ in 0..32 {
+            decoder.value <<= 1;
+            if decoder.get_bit_and_shift()? {
+                decoder.value += 1;
+            }
+        }
+        Ok(decoder)
+    }
+
+    /**
+        Returns the inner reader
+    */
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /**
+        Read one bit and shift position, refilling buffer from inner reader
+
+        Past the end of coded data zeros are read, but never more than the
+        32 bits that can follow the last encoded symbol. Last bytes of the
+        buffer are kept on refill, so bytes following the code are still
+        available to `read_trailer`.
+    */
+    fn get_bit_and_shift(&mut self) -> Result<bool, Error> {
+        self.consumed += 1;
+        if self.position == self.buffer.len() * 8 {
+            if self.exhausted {
+                self.missing += 1;
+                if self.missing > 32 {
+                    return Err(Error::Truncated);
+                }
+                return Ok(false);
+            }
+            let keep = self.buffer.len().min(Self::KEEP);
+            self.buffer.drain(..self.buffer.len() - keep);
+            self.buffer.resize(Self::BUFFER_SIZE, 0);
+            let n = loop {
+                match self.inner.read(&mut self.buffer[keep..]) {
+                    Ok(n) => break n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(Error::Io(e)),
+                }
+            };
+            self.buffer.truncate(keep + n);
+            self.fetched += n as u64;
+            self.position = keep * 8;
+            if n == 0 {
+                self.exhausted = true;
+                self.missing = 1;
+                return Ok(false);
+            }
+        }
+        let bit = self.buffer[self.position / 8] & Self::BIN[self.position % 8] != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    /**
+        Fills `buf` with bytes directly following the code
+
+        Must be called once, after the last symbol was decoded. Decoder
+        reads 32 bits ahead while the code ends with two terminating bits,
+        which gives its length in bytes.
+    */
+    pub(crate) fn read_trailer(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let end = (self.consumed - 30).div_ceil(8);
+        if end > self.fetched {
+            return Err(Error::Truncated);
+        }
+        let start = self.buffer.len() - (self.fetched - end) as usize;
+        let n = (self.buffer.len() - start).min(buf.len());
+        buf[..n].copy_from_slice(&self.buffer[start..start + n]);
+        self.buffer.truncate(start + n);
+        if let Err(e) = self.inner.read_exact(&mut buf[n..]) {
+            return Err(match e.kind() {
+                io::ErrorKind::UnexpectedEof => Error::Truncated,
+                _ => Error::Io(e),
+            });
+        }
+        Ok(())
+    }
+
+    /**
+        Cumulative frequency out of `total` pointed to by the code
+    */
+    pub(crate) fn frequency(&self, total: u64) -> u64 {
+        let range = self.high as u64 - (self.low as u64) + 1;
+        ((self.value as u64 - self.low as u64 + 1) * total - 1) / range
+    }
+
+    /**
+        Narrows interval to cumulative frequencies `start..end` out of `total`
+    */
+    pub(crate) fn decode(&mut self, start: u64, end: u64, total: u64) -> Result<(), Error> {
+        let range = self.high as u64 - (self.low as u64) + 1;
+        self.high = (self.low as u64 + (range * end) / total - 1) as u32;
+        self.low = (self.low as u64 + (range * start) / total) as u32;
+        loop {
+            if self.high < 0x80000000 {
+                //do nothing, bit is a zero
+            } else if self.low >= 0x80000000 {
+                self.value -= 0x80000000;  //subtract one half from all three code values
+                self.low -= 0x80000000;
+                self.high -= 0x80000000;
+            } else if self.low >= 0x40000000 && self.high < 0xC0000000 {
+                self.value -= 0x40000000;
+                self.low -= 0x40000000;
+                self.high -= 0x40000000;
+            } else {
+                return Ok(());
+            }
+            self.low <<= 1;
+            self.high <<= 1;
+            self.high += 1;
+            self.value <<= 1;
+            if self.get_bit_and_shift()? {
+                self.value += 1;
+            }
+        }
+    }
+
+    /**
+        Decodes whether stream ends here, see `RangeEncoder::encode_end`
+    */
+    pub(crate) fn decode_end(&mut self) -> Result<bool, Error> {
+        if self.frequency(MAX_TOTAL) >= END {
+            self.decode(END, MAX_TOTAL, MAX_TOTAL)?;
+            Ok(true)
+        } else {
+            self.decode(0, END, MAX_TOTAL)?;
+            Ok(false)
+        }
+    }
+}
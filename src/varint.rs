@@ -0,0 +1,47 @@
+use std::io::{self, Read, Write};
+
+use crate::error::Error;
+
+/**
+    Writes `value` as LEB128 varint: seven bits per byte, least significant
+    first, with the high bit set on all bytes but the last
+*/
+pub(crate) fn write_varint<W: Write>(w: &mut W, mut value: u64) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(10);
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x80);
+    }
+    w.write_all(&bytes)
+}
+
+/**
+    Reads varint written by `write_varint`
+*/
+pub(crate) fn read_varint<R: Read>(r: &mut R) -> Result<u64, Error> {
+    let mut value = 0_u64;
+    for i in 0..10 {
+        let mut byte = [0];
+        if let Err(e) = r.read_exact(&mut byte) {
+            return Err(match e.kind() {
+                io::ErrorKind::UnexpectedEof => Error::Truncated,
+                _ => Error::Io(e),
+            });
+        }
+        let bits = (byte[0] & 0x7F) as u64;
+        if i == 9 && bits > 1 {
+            //Value does not fit in 64 bits
+            return Err(Error::Corrupt);
+        }
+        value |= bits << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Corrupt)
+}